@@ -1,5 +1,5 @@
 import React from 'react';
-import { CellStatus, MemoryCell } from './types';
+import { CardTable, CellStatus, LargeObjectSpaceInfo, MemoryCell, Regions, SegmentPointer, Tlabs, BuddyInfo } from './types';
 import './HeapGrid.css';
 import { AnimatedCell } from './useHeapAnimation';
 
@@ -7,21 +7,114 @@ interface HeapGridProps {
     memory: MemoryCell[];
     highlightedCells: number[];
     animatedCells: AnimatedCell[];
+    regions?: Regions | null;
+    segmentPointers?: Partial<Record<SegmentPointer, number>>;
+    largeObjectSpace?: LargeObjectSpaceInfo;
+    cardTable?: CardTable;
+    tlabs?: Tlabs;
+    buddy?: BuddyInfo;
 }
 
 const cellStyleMap: Record<CellStatus, string> = {
     [CellStatus.Free]: 'white',
     [CellStatus.Allocated]: '#7CCD7C',
     [CellStatus.Marked]: 'yellow',
+    [CellStatus.Grey]: '#A9A9A9',
+    [CellStatus.Pinned]: '#4682B4',
     [CellStatus.Used]: '#228B22',
+    [CellStatus.Header]: '#708090',
 };
 
-const HeapGrid: React.FC<HeapGridProps> = ({ memory, highlightedCells, animatedCells }) => {
+// Low three bits of a tagged word, see src/word.rs
+const WORD_TAGS = ['free', 'scalar', 'ref', 'weak ref', 'ephemeron key', 'ephemeron value', 'header', 'forward'];
+
+const describeWord = (word: number): string => {
+    const tag = word % 8;
+    const payload = Math.floor(word / 8);
+    const hex = (n: number) => `0x${n.toString(16).toUpperCase()}`;
+    // pointer payloads are the address plus one, zero being null
+    const pointer = payload === 0 ? 'null' : hex(payload - 1);
+    const decoded = [
+        '',
+        `${payload}`,
+        pointer,
+        pointer,
+        pointer,
+        pointer,
+        `size ${Math.floor(payload / 2)}${payload % 2 === 1 ? ', marked' : ''}`,
+        `to ${hex(payload)}`,
+    ][tag];
+    return `${hex(word)} 0b${word.toString(2)}\n${WORD_TAGS[tag]} ${decoded}`.trimEnd();
+}
+
+const HeapGrid: React.FC<HeapGridProps> = ({ memory, highlightedCells, animatedCells, regions, segmentPointers, largeObjectSpace, cardTable, tlabs, buddy }) => {
     const memoryLen = memory.length;
     const numCols = Math.ceil(Math.sqrt(memoryLen));
 
     const isFirstHighlighted = (index: number) => highlightedCells[0] === index;
 
+    // Block and line boundaries of mark-region heaps
+    const regionClasses = (index: number): string => {
+        if (!regions) return '';
+        const classes = [];
+        if (index % regions.block_size === 0) {
+            classes.push('block-start');
+        } else if (index % regions.line_size === 0) {
+            classes.push('line-start');
+        }
+        if (regions.line_marks[Math.floor(index / regions.line_size)]) {
+            classes.push('line-marked');
+        }
+        return classes.join(' ');
+    }
+
+    // Pages of the large object space at the end of the heap
+    const largeObjectClasses = (index: number): string => {
+        if (!largeObjectSpace || index < largeObjectSpace.start) return '';
+        const offset = index - largeObjectSpace.start;
+        return offset % largeObjectSpace.page_size === 0 ? 'large-object page-start' : 'large-object';
+    }
+
+    // Cells on cards dirtied by pointer stores
+    const cardClasses = (index: number): string => {
+        if (!cardTable) return '';
+        return cardTable.cards[Math.floor(index / cardTable.card_size)] ? 'card-dirty' : '';
+    }
+
+    // Thread-local allocation buffers, the part left to bump through is dimmed
+    const tlabClasses = (index: number): string => {
+        if (!tlabs) return '';
+        const tlab = Object.values(tlabs.buffers).find(b => b.start <= index && index < b.end);
+        if (!tlab) return '';
+        return index >= tlab.top ? 'tlab tlab-free' : 'tlab';
+    }
+
+    // Buddy blocks: the two halves of a pair are told apart by their shade,
+    // words an object was rounded up past are hatched
+    const buddyClasses = (index: number): string => {
+        if (!buddy) return '';
+        const blocks: [number, number, number][] = [
+            ...buddy.allocated_blocks.map(b => [b.start, b.size, b.wasted] as [number, number, number]),
+            ...buddy.free_blocks.map(([start, size]) => [start, size, 0] as [number, number, number]),
+        ];
+        const block = blocks.find(([start, size]) => start <= index && index < start + size);
+        if (!block) return '';
+        const [start, size, wasted] = block;
+        const classes = [(start / size) % 2 === 0 ? 'buddy-lower' : 'buddy-upper'];
+        if (index === start) classes.push('buddy-start');
+        if (index >= start + size - wasted) classes.push('buddy-waste');
+        return classes.join(' ');
+    }
+
+    // Treadmill pointers sitting on a cell, by their initials
+    const pointerLabels = (index: number): string | undefined => {
+        if (!segmentPointers) return undefined;
+        const labels = Object.entries(segmentPointers)
+            .filter(([, addr]) => addr === index)
+            .map(([pointer]) => pointer[0]);
+        return labels.length > 0 ? labels.join('') : undefined;
+    }
+
     const getAnimationStyle = (index: number): React.CSSProperties => {
         const cellAnimation: AnimatedCell | undefined = animatedCells.find(anim => anim.cellIndex === index);
         if (!cellAnimation) return {};
@@ -59,12 +152,14 @@ const HeapGrid: React.FC<HeapGridProps> = ({ memory, highlightedCells, animatedC
                 return (
                     <div
                         key={index}
-                        className={`cell ${isHighlighted ? 'highlighted' : ''}`}
+                        className={`cell ${isHighlighted ? 'highlighted' : ''} ${regionClasses(index)} ${largeObjectClasses(index)} ${cardClasses(index)} ${tlabClasses(index)} ${buddyClasses(index)}`}
                         style={{
                             ...animationStyle,
                             backgroundColor: cellStyleMap[cell.status]
                         }}
                         data-address={isFirstHighlighted(index) ? `0x${index.toString(16).toUpperCase()}` : undefined}
+                        data-pointers={pointerLabels(index)}
+                        title={cell.word !== undefined ? describeWord(cell.word) : undefined}
                     />
                 );
             })}
@@ -1,15 +1,55 @@
 import React from 'react';
 import './InfoBlock.css';
-import { InfoBlockData } from './types';
+import { CardTableStats, InfoBlockData, TlabStats } from './types';
 
-const InfoBlock: React.FC<InfoBlockData> = ({ gc_type, alignment, heap_size, allocd_objects, free_memory }) => {
+interface InfoBlockProps extends InfoBlockData {
+    // Card table cost of the last collection
+    card_stats?: CardTableStats | null;
+    // TLABs retired by the last collection
+    tlab_stats?: TlabStats | null;
+}
+
+const InfoBlock: React.FC<InfoBlockProps> = ({ gc_type, alignment, heap_size, alloc_policy, allocd_objects, free_memory, header_words, large_object_space, card_table, size_classes, tlabs, buddy, card_stats, tlab_stats }) => {
     return (
         <div className='info-block'>
             <InfoEntry label={'GC Type'} value={gc_type} />
             <InfoEntry label={'Alignment'} value={`${alignment}`} />
             <InfoEntry label={'Heap size'} value={`${heap_size}`} />
+            <InfoEntry label={'Allocation policy'} value={alloc_policy} />
             <InfoEntry label={'Allocated objects'} value={`${allocd_objects}`} />
             <InfoEntry label={'Free memory'} value={`${free_memory}`} />
+            {header_words > 0 && <InfoEntry label={'Header words'} value={`${header_words}`} />}
+            {large_object_space && <>
+                <InfoEntry label={'Large objects'} value={`${large_object_space.objects}`} />
+                <InfoEntry label={'Large object pages'} value={`${large_object_space.used_pages}/${large_object_space.pages}`} />
+            </>}
+            {size_classes && size_classes.map(sizeClass => (
+                <InfoEntry key={sizeClass.size}
+                    label={`Size class ${sizeClass.size}`}
+                    value={`${sizeClass.used_cells}/${sizeClass.used_cells + sizeClass.free_cells} cells`} />
+            ))}
+            {buddy && <>
+                <InfoEntry label={'Buddy blocks'} value={`${buddy.allocated_blocks.length} used, ${buddy.free_blocks.length} free`} />
+                <InfoEntry label={'Internal fragmentation'} value={`${buddy.wasted} words`} />
+                {buddy.allocated_blocks.filter(block => block.wasted > 0).map(block => (
+                    <InfoEntry key={block.start}
+                        label={`Block 0x${block.start.toString(16)} (${block.size})`}
+                        value={`${block.wasted} wasted`} />
+                ))}
+            </>}
+            {card_table && <>
+                <InfoEntry label={'Dirty cards'} value={`${card_table.cards.filter(dirty => dirty).length}/${card_table.cards.length}`} />
+                <InfoEntry label={'Cards dirtied'} value={`${card_table.dirtied}`} />
+            </>}
+            {tlabs && <>
+                <InfoEntry label={'TLABs in use'} value={`${Object.keys(tlabs.buffers).length} x ${tlabs.size} words`} />
+                <InfoEntry label={'TLABs retired'} value={`${tlabs.retired} (${tlabs.waste} words wasted)`} />
+            </>}
+            {tlab_stats && <InfoEntry label={'TLAB waste before GC'} value={`${tlab_stats.waste} words in ${tlab_stats.retired} TLABs`} />}
+            {card_stats && <>
+                <InfoEntry label={'Cards dirtied before GC'} value={`${card_stats.dirtied}`} />
+                <InfoEntry label={'Cards scanned by GC'} value={`${card_stats.scanned}`} />
+            </>}
         </div>
     );
 }
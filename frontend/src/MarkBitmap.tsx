@@ -0,0 +1,18 @@
+import React from 'react';
+import './MarkBitmap.css';
+import { MarkBitmap } from './types';
+
+const MarkBitmapTable: React.FC<MarkBitmap> = ({ granularity, bits }) => {
+    return (
+        <div className='mark-bitmap'>
+            <div className='mark-bitmap-title'>Mark bitmap: {granularity} word(s) per bit</div>
+            <div className='mark-bitmap-bits'>
+                {bits.map((bit, i) => (
+                    <div key={i} className={`mark-bitmap-bit ${bit ? 'marked' : ''}`} title={`${i * granularity}`} />
+                ))}
+            </div>
+        </div>
+    );
+}
+
+export default MarkBitmapTable;
@@ -0,0 +1,25 @@
+import React from 'react';
+import './SideTables.css';
+import { SideTablesData } from './types';
+
+const SideTables: React.FC<SideTablesData> = ({ mark_bitmap, block_size, offset_table }) => {
+    return (
+        <div className='side-tables'>
+            <div className='side-tables-title'>Mark bitmap</div>
+            <div className='side-tables-bitmap'>
+                {offset_table.map((offset, block) => (
+                    <div className='side-tables-block' key={block}>
+                        <div className='side-tables-bits'>
+                            {mark_bitmap.slice(block * block_size, (block + 1) * block_size).map((bit, i) => (
+                                <div key={i} className={`side-tables-bit ${bit ? 'live' : ''}`} />
+                            ))}
+                        </div>
+                        <div className='side-tables-offset'>{offset}</div>
+                    </div>
+                ))}
+            </div>
+        </div>
+    );
+}
+
+export default SideTables;
@@ -5,8 +5,11 @@ import './Visualization.css';
 import InfoBlock from './InfoBlock';
 import EventStream from './EventStream';
 import HeapGrid from './HeapGrid';
+import SideTables from './SideTables';
+import WorkerStatsTable from './WorkerStats';
+import MarkBitmapTable from './MarkBitmap';
 import ControlPanel from './ControlPanel';
-import { CellStatus, MemoryCell, RESET_MSG, STEP_MSG, TICK_MSG, InfoBlockData, INFOBLOCK_DEFAULT, GCEvent, LogEntry, WSMsgRequest } from './types';
+import { CellStatus, MemoryCell, RESET_MSG, STEP_MSG, TICK_MSG, InfoBlockData, INFOBLOCK_DEFAULT, CardTableStats, TlabStats, GCEvent, MarkBitmap, ParallelMarkStats, SideTablesData, Regions, SegmentPointer, LogEntry, WSMsgRequest } from './types';
 import Slider from './Slider';
 import Toast from './Toast';
 
@@ -41,6 +44,13 @@ const Visualization: React.FC = () => {
     const { program_name } = useParams<{ program_name?: string }>();
     const [toastMessage, setToastMessage] = useState<string>('');
     const [infoBlock, setInfoBlock] = useState<InfoBlockData>(INFOBLOCK_DEFAULT);
+    const [sideTables, setSideTables] = useState<SideTablesData | null>(null);
+    const [parallelMark, setParallelMark] = useState<ParallelMarkStats | null>(null);
+    const [markBitmap, setMarkBitmap] = useState<MarkBitmap | null>(null);
+    const [cardStats, setCardStats] = useState<CardTableStats | null>(null);
+    const [tlabStats, setTlabStats] = useState<TlabStats | null>(null);
+    const [regions, setRegions] = useState<Regions | null>(null);
+    const [segmentPointers, setSegmentPointers] = useState<Partial<Record<SegmentPointer, number>>>({});
 
     const toggleExecution = () => {
         if (isHalt) return;
@@ -55,6 +65,13 @@ const Visualization: React.FC = () => {
         setEventLogs([SUGGEST_INIT_LOG_ENTRY]);
         setPendingGCEvents([]);
         setGCEventLogs([]);
+        setSideTables(null);
+        setParallelMark(null);
+        setMarkBitmap(null);
+        setCardStats(null);
+        setTlabStats(null);
+        setRegions(null);
+        setSegmentPointers({});
         clearHighlightedCells();
         clearAnimations();
     }
@@ -102,15 +119,45 @@ const Visualization: React.FC = () => {
                         setIsRunning(false);
                     }
 
+                    // GC instructions, and allocations and mutator writes under reference
+                    // counting, carry collector events that are replayed one by one
+                    const gcEvents: GCEvent[] = data.instr_result.gc_eventlog ?? [];
+                    const hasGCEvents = data.instr_result._type === "GC" || gcEvents.length > 0;
+
                     if (data.info_block
                         // for gc event we update the state in the client so no need to set it
                         //(although we could potentially add some ASSERT here)
-                        && data.instr_result._type !== "GC") {
+                        && !hasGCEvents) {
                         setInfoBlock(data.info_block)
                     }
 
-                    if (data.instr_result._type === "GC") {
-                        setPendingGCEvents(data.instr_result.gc_eventlog);
+                    if (data.instr_result._type === "GC" && data.instr_result.side_tables) {
+                        setSideTables(data.instr_result.side_tables);
+                    }
+                    // an allocation that did not fit may have triggered a collection
+                    const gcStats = data.instr_result._type === "GC" ? data.instr_result.stats
+                        : data.instr_result._type === "Allocate" ? data.instr_result.gc_stats
+                            : undefined;
+                    if (gcStats?.parallel_mark) {
+                        setParallelMark(gcStats.parallel_mark);
+                    }
+                    if (gcStats?.card_table) {
+                        setCardStats(gcStats.card_table);
+                    }
+                    if (gcStats?.tlab) {
+                        setTlabStats(gcStats.tlab);
+                    }
+                    if (data.instr_result._type === "GC" && data.instr_result.mark_bitmap) {
+                        // marking starts from a cleared bitmap, bits are set as objects get marked
+                        const { granularity, bits } = data.instr_result.mark_bitmap;
+                        setMarkBitmap({ granularity, bits: bits.map(() => false) });
+                    }
+                    if (data.instr_result._type === "GC" && data.instr_result.regions) {
+                        setRegions(data.instr_result.regions);
+                    }
+
+                    if (hasGCEvents) {
+                        setPendingGCEvents(gcEvents);
                     } else {
                         if (data.memory) {
                             setMemory(data.memory);
@@ -140,7 +187,7 @@ const Visualization: React.FC = () => {
         // Process a single GC event if any
         if (pendingGCEvents.length > 0) {
             const alterHeapState = (event: GCEvent): void => {
-                if (event.type === 'FreeObject') {
+                if (event.type === 'FreeObject' || event.type === 'ReclaimFinalized') {
                     const [cellIndex] = cellIndexesFromEvent(event)!;
                     setMemory(memory.map((c, i) => {
                         if (i === cellIndex) {
@@ -148,7 +195,11 @@ const Visualization: React.FC = () => {
                         }
                         return c
                     }));
-                } else if (event.type === 'MarkObject') {
+                } else if ((event.type === 'MarkObject' || event.type === 'ResurrectObject') && markBitmap) {
+                    // object headers stay untouched, the mark goes into the bitmap
+                    const bit = Math.floor(event.addr / markBitmap.granularity);
+                    setMarkBitmap({ ...markBitmap, bits: markBitmap.bits.map((b, i) => b || i === bit) });
+                } else if (event.type === 'MarkObject' || event.type === 'ResurrectObject') {
                     const [cellIndex] = cellIndexesFromEvent(event)!;
                     setMemory(memory.map((c, i) => {
                         if (i === cellIndex) {
@@ -156,7 +207,15 @@ const Visualization: React.FC = () => {
                         }
                         return c
                     }));
-                } else if (event.type === 'MoveObject') {
+                } else if (event.type === 'ShadeObject') {
+                    const { addr, size } = event;
+                    setMemory(memory.map((c, i) => {
+                        if (i >= addr && i < addr + size) {
+                            c.status = CellStatus.Grey;
+                        }
+                        return c;
+                    }));
+                } else if (event.type === 'MoveObject' || event.type === 'PromoteObject') {
                     const { from, to, size } = event;
                     setMemory(memory.map((c, i) => {
                         if (i >= from && i < from + size) {
@@ -166,6 +225,15 @@ const Visualization: React.FC = () => {
                         }
                         return c;
                     }));
+                } else if (event.type === 'ResizeHeap') {
+                    // the grid lays itself out again for the new number of cells
+                    const { to } = event;
+                    setMemory(to > memory.length
+                        ? [...memory, ...Array.from({ length: to - memory.length }, () => ({ status: CellStatus.Free }))]
+                        : memory.slice(0, to));
+                } else if (event.type === 'MoveSegmentPointer') {
+                    const { pointer, to } = event;
+                    setSegmentPointers(prevPointers => ({ ...prevPointers, [pointer]: to }));
                 }
             }
             const currentGCEvent: GCEvent = pendingGCEvents[0];
@@ -191,7 +259,7 @@ const Visualization: React.FC = () => {
             setGCEventLogs([]);
             ws.send(JSON.stringify(msg));
         }
-    }, [setGCEventLogs, enqueueAnimation, pendingGCEvents, ws, memory, infoBlock]);
+    }, [setGCEventLogs, enqueueAnimation, pendingGCEvents, ws, memory, infoBlock, markBitmap]);
 
     useEffect(() => {
         let intervalId: any = null;
@@ -230,8 +298,17 @@ const Visualization: React.FC = () => {
                     <InfoBlock gc_type={infoBlock.gc_type}
                         alignment={infoBlock.alignment}
                         heap_size={infoBlock.heap_size}
+                        alloc_policy={infoBlock.alloc_policy}
                         allocd_objects={infoBlock.allocd_objects}
                         free_memory={infoBlock.free_memory}
+                        header_words={infoBlock.header_words}
+                        large_object_space={infoBlock.large_object_space}
+                        card_table={infoBlock.card_table}
+                        size_classes={infoBlock.size_classes}
+                        card_stats={cardStats}
+                        tlabs={infoBlock.tlabs}
+                        buddy={infoBlock.buddy}
+                        tlab_stats={tlabStats}
                     />
                     <Slider minValue={100} maxValue={2000} intervalRate={intervalRate} updateIntervalRate={setIntervalRate} />
                     <EventStream
@@ -249,9 +326,12 @@ const Visualization: React.FC = () => {
                         clearHighlightedCells={clearHighlightedCells}
                     />}
 
+                    {sideTables && <SideTables {...sideTables} />}
+                    {parallelMark && <WorkerStatsTable {...parallelMark} />}
+                    {markBitmap && <MarkBitmapTable {...markBitmap} />}
                     <div className='extra-details'></div>
                 </div>
-                <HeapGrid memory={memory} highlightedCells={highlightedCells} animatedCells={animatedCells} />
+                <HeapGrid memory={memory} highlightedCells={highlightedCells} animatedCells={animatedCells} regions={regions} segmentPointers={segmentPointers} largeObjectSpace={infoBlock.large_object_space} cardTable={infoBlock.card_table} tlabs={infoBlock.tlabs} buddy={infoBlock.buddy} />
             </div>
             <ControlPanel isRunning={isRunning}
                 toggleExecution={toggleExecution}
@@ -268,17 +348,19 @@ const resetInfoBlock = (infoBlock: InfoBlockData, heapSize: number): InfoBlockDa
         gc_type: infoBlock.gc_type,
         alignment: infoBlock.alignment,
         heap_size: infoBlock.heap_size,
+        alloc_policy: infoBlock.alloc_policy,
         allocd_objects: 0,
         free_memory: heapSize,
+        header_words: 0,
     }
 }
 
 const eventHasAnimation = (gcevent: GCEvent): boolean => {
-    return ['MarkObject', 'FreeObject'].includes(gcevent.type)
+    return ['MarkObject', 'FreeObject', 'IncRef', 'DecRef', 'ResurrectObject', 'ReclaimFinalized'].includes(gcevent.type)
 }
 
 const hasEffectOnInfoBlock = (gcevent: GCEvent): boolean => {
-    return ['FreeObject'].includes(gcevent.type)
+    return ['FreeObject', 'ReclaimFinalized', 'ScanCard', 'NewTlab', 'RetireTlab', 'ResizeHeap'].includes(gcevent.type)
 }
 
 const animationFromGCEvent = (event: GCEvent): TimedAnimation => {
@@ -286,11 +368,17 @@ const animationFromGCEvent = (event: GCEvent): TimedAnimation => {
 
     switch (event.type) {
         case "MarkObject":
+        case "ResurrectObject":
             animation = createTimedAnimation(500, 'flashing', 0.5);
             break;
         case "FreeObject":
+        case "ReclaimFinalized":
             animation = createTimedAnimation(500, 'flickering', 0.5);
             break;
+        case "IncRef":
+        case "DecRef":
+            animation = createTimedAnimation(300, 'flashing', 0.7);
+            break;
         default:
             throw new Error(`Unsupported GCEvent: ${event}`);
     }
@@ -301,12 +389,37 @@ const animationFromGCEvent = (event: GCEvent): TimedAnimation => {
 const cellIndexesFromEvent = (event: GCEvent): number[] => {
     switch (event.type) {
         case 'MarkObject':
+        case 'ShadeObject':
         case 'FreeObject':
+        case 'IncRef':
+        case 'DecRef':
+        case 'ResurrectObject':
+        case 'EnqueueFinalizer':
+        case 'RunFinalizer':
+        case 'ReclaimFinalized':
+        case 'SkipPinned':
             return [event.addr];
         case 'MoveObject':
+        case 'PromoteObject':
             return [event.from, event.to];
         case 'UpdateFwdPtr':
             return [event.old, event.new];
+        case 'ThreadPointer':
+            return [event.obj, event.field];
+        case 'UnthreadPointer':
+            return [event.field];
+        case 'MoveSegmentPointer':
+            return [event.to];
+        case 'ClearWeakRef':
+            return [event.field];
+        case 'StealWork':
+            return [event.addr];
+        case 'ClearEphemeron':
+            return [event.field, event.field + 1];
+        case 'ScanCard':
+        case 'NewTlab':
+        case 'RetireTlab':
+            return [event.start];
         case 'GCPhase':
         default:
             return [];
@@ -315,12 +428,55 @@ const cellIndexesFromEvent = (event: GCEvent): number[] => {
 
 const newInfoBlockFromGCEvent = (infoBlock: InfoBlockData, gcevent: GCEvent): InfoBlockData => {
     console.info('before', infoBlock)
-    if (gcevent.type === 'FreeObject') {
-        infoBlock.free_memory += gcevent.size;
+    if (gcevent.type === 'FreeObject' || gcevent.type === 'ReclaimFinalized') {
+        const los = infoBlock.large_object_space;
+        if (los && gcevent.addr >= los.start) {
+            // Large objects give back whole pages instead of free memory
+            los.used_pages -= Math.ceil(gcevent.size / los.page_size);
+            los.objects--;
+            return infoBlock;
+        }
+        const sizeClass = infoBlock.size_classes?.find(c => gcevent.size <= c.size);
+        const buddyBlock = infoBlock.buddy?.allocated_blocks.find(b => b.start === gcevent.addr);
+        if (infoBlock.buddy && buddyBlock) {
+            // the whole block is freed, coalescing shows up on the next refresh
+            infoBlock.buddy.allocated_blocks = infoBlock.buddy.allocated_blocks.filter(b => b !== buddyBlock);
+            infoBlock.buddy.free_blocks.push([buddyBlock.start, buddyBlock.size]);
+            infoBlock.buddy.wasted -= buddyBlock.wasted;
+            infoBlock.free_memory += buddyBlock.size;
+        } else if (sizeClass) {
+            // the whole cell goes back to its size class
+            sizeClass.used_cells--;
+            sizeClass.free_cells++;
+            infoBlock.free_memory += sizeClass.size;
+        } else {
+            infoBlock.free_memory += gcevent.size;
+        }
         infoBlock.allocd_objects--;
         console.info('After', infoBlock)
         return infoBlock;
     }
+    if (gcevent.type === 'ScanCard' && infoBlock.card_table) {
+        // scanned cards are clean again
+        infoBlock.card_table.cards[gcevent.card] = false;
+        return infoBlock;
+    }
+    if (gcevent.type === 'NewTlab' && infoBlock.tlabs) {
+        const { thread, start, end } = gcevent;
+        infoBlock.tlabs.buffers[thread] = { start, top: start, end };
+        return infoBlock;
+    }
+    if (gcevent.type === 'RetireTlab' && infoBlock.tlabs) {
+        delete infoBlock.tlabs.buffers[gcevent.thread];
+        infoBlock.tlabs.retired++;
+        infoBlock.tlabs.waste += gcevent.waste;
+        return infoBlock;
+    }
+    if (gcevent.type === 'ResizeHeap') {
+        infoBlock.free_memory += gcevent.to - gcevent.from;
+        infoBlock.heap_size = gcevent.to;
+        return infoBlock;
+    }
     // TODO: MoveObject
     throw new Error('newInfoBlockFromGCEvent unexpected gcevent');
 }
@@ -0,0 +1,35 @@
+import React from 'react';
+import './WorkerStats.css';
+import { ParallelMarkStats } from './types';
+
+const WorkerStatsTable: React.FC<ParallelMarkStats> = ({ rounds, workers }) => {
+    return (
+        <div className='worker-stats'>
+            <div className='worker-stats-title'>Parallel mark: {rounds} rounds</div>
+            <table>
+                <thead>
+                    <tr>
+                        <th>Worker</th>
+                        <th>Marked</th>
+                        <th>Words</th>
+                        <th>Steals</th>
+                        <th>Idle</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    {workers.map((worker, id) => (
+                        <tr key={id}>
+                            <td>{id}</td>
+                            <td>{worker.marked}</td>
+                            <td>{worker.marked_words}</td>
+                            <td>{worker.steals}</td>
+                            <td>{worker.idle_rounds}</td>
+                        </tr>
+                    ))}
+                </tbody>
+            </table>
+        </div>
+    );
+}
+
+export default WorkerStatsTable;
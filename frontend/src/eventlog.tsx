@@ -15,8 +15,10 @@ export const logEntryOps = (log: LogEntry): EventOps => ({
                 return Array.from({ length: ir.object.fields.length }, (_, i) => ir.addr + i);
             case 'Write':
             case 'Read':
+            case 'Pin':
                 return [ir.addr];
             case 'GC':
+            case 'RunFinalizers':
                 return [];
         }
     },
@@ -36,9 +38,37 @@ export const gcEventOps = (event: GCEvent): EventOps => ({
     cellsToHighlight: () => {
         switch (event.type) {
             case "MarkObject":
+            case "ShadeObject":
             case "FreeObject":
+            case "IncRef":
+            case "DecRef":
+            case "ChangeColor":
+            case "ResurrectObject":
+            case "EnqueueFinalizer":
+            case "RunFinalizer":
+            case "ReclaimFinalized":
                 return [event.addr];
+            case "SkipPinned":
+                return Array.from({ length: event.size }, (_, i) => event.addr + i);
+            case "ScanCard":
+                return Array.from({ length: event.size }, (_, i) => event.start + i);
+            case "NewTlab":
+            case "RetireTlab":
+                return Array.from({ length: event.end - event.start }, (_, i) => event.start + i);
+            case "ThreadPointer":
+                return [event.obj, event.field];
+            case "UnthreadPointer":
+                return [event.field];
+            case "MoveSegmentPointer":
+                return [event.to];
+            case "ClearWeakRef":
+                return [event.field];
+            case "StealWork":
+                return [event.addr];
+            case "ClearEphemeron":
+                return [event.field, event.field + 1];
             case "MoveObject":
+            case "PromoteObject":
                 return Array.from({ length: event.size }, (_, i) => event.to + i);
             default:
                 return [];
@@ -50,8 +80,18 @@ export const gcEventOps = (event: GCEvent): EventOps => ({
             case "GCPhase":
                 message = event.msg;
                 break;
+            case "ResizeHeap":
+                message = `${event.to > event.from ? 'Grow' : 'Shrink'} heap from ${event.from} to ${event.to} words`;
+                break;
+            case "AllocationFailure":
+                message = `Allocation of ${event.size} word(s) failed, collecting garbage`;
+                break;
             case "MarkObject":
                 message = `Marked Object at address (0x${event.addr.toString(16)})`;
+                if (event.worker !== undefined) message += ` on worker ${event.worker}`;
+                break;
+            case "ShadeObject":
+                message = `Shaded Object at address (0x${event.addr.toString(16)}) grey`;
                 break;
             case "FreeObject":
                 message = `Freed Object at address (0x${event.addr.toString(16)})`;
@@ -62,6 +102,69 @@ export const gcEventOps = (event: GCEvent): EventOps => ({
             case "UpdateFwdPtr":
                 message = `Update Forward Pointer (0x${event.old.toString(16)}) -> (0x${event.new.toString(16)})`;
                 break;
+            case "ThreadPointer":
+                message = `Thread field (0x${event.field.toString(16)}) onto header of (0x${event.obj.toString(16)})`;
+                break;
+            case "UnthreadPointer":
+                message = `Unthread field (0x${event.field.toString(16)}) -> (0x${event.new.toString(16)})`;
+                break;
+            case "CollectGeneration":
+                message = event.generation === "Young" ? "Minor collection (nursery)" : "Major collection (whole heap)";
+                break;
+            case "PromoteObject":
+                message = `Promote object (0x${event.from.toString(16)}) -> (0x${event.to.toString(16)})`;
+                break;
+            case "IncRef":
+                message = `Increment count of (0x${event.addr.toString(16)}) to ${event.count}`;
+                break;
+            case "DecRef":
+                message = `Decrement count of (0x${event.addr.toString(16)}) to ${event.count}`;
+                break;
+            case "MarkLine":
+                message = `Marked line ${event.line}`;
+                break;
+            case "MoveSegmentPointer":
+                message = `Move ${event.pointer} (0x${event.from.toString(16)}) -> (0x${event.to.toString(16)})`;
+                break;
+            case "ClearWeakRef":
+                message = `Clear weak field (0x${event.field.toString(16)}) to reclaimed (0x${event.target.toString(16)})`;
+                break;
+            case "EphemeronIteration":
+                message = `Ephemeron iteration ${event.iteration}: ${event.ready} value(s) became reachable`;
+                break;
+            case "ClearEphemeron":
+                message = `Clear ephemeron with dead key at (0x${event.field.toString(16)})`;
+                break;
+            case "ResurrectObject":
+                message = `Resurrect finalizable Object at address (0x${event.addr.toString(16)})`;
+                break;
+            case "EnqueueFinalizer":
+                message = `Queue finalizer of (0x${event.addr.toString(16)})`;
+                break;
+            case "RunFinalizer":
+                message = `Run finalizer of (0x${event.addr.toString(16)})`;
+                break;
+            case "ReclaimFinalized":
+                message = `Reclaim finalized Object at address (0x${event.addr.toString(16)})`;
+                break;
+            case "StealWork":
+                message = `Worker ${event.thief} stole (0x${event.addr.toString(16)}) from worker ${event.victim}`;
+                break;
+            case "SkipPinned":
+                message = `Object at address (0x${event.addr.toString(16)}) is pinned, not moved`;
+                break;
+            case "ScanCard":
+                message = `Scan dirty card ${event.card} (0x${event.start.toString(16)})`;
+                break;
+            case "NewTlab":
+                message = `Thread ${event.thread} gets TLAB (0x${event.start.toString(16)})..(0x${event.end.toString(16)})`;
+                break;
+            case "RetireTlab":
+                message = `Retire TLAB of thread ${event.thread}, ${event.waste} word(s) wasted`;
+                break;
+            case "ChangeColor":
+                message = `Colour (0x${event.addr.toString(16)}) ${event.color}`;
+                break;
         }
         return (
             <> [
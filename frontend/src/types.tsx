@@ -2,27 +2,89 @@ export enum CellStatus {
     Free = "Free",
     Allocated = "Allocated",
     Marked = "Marked",
-    Used = "Used"
+    Grey = "Grey",
+    Pinned = "Pinned",
+    Used = "Used",
+    Header = "Header"
 }
 
 export type InstrResult
-    = { _type: 'Allocate'; addr: number, object: Object }
-    | { _type: 'Read'; addr: number }
-    | { _type: 'Write'; addr: number, value: Value }
-    | { _type: 'GC', gc_eventlog: GCEvent[] }
+    = { _type: 'Allocate'; addr: number, object: Object, gc_eventlog: GCEvent[], gc_stats?: GCStats }
+    | { _type: 'Read'; addr: number, gc_eventlog: GCEvent[] }
+    | { _type: 'Write'; addr: number, value: Value, gc_eventlog: GCEvent[] }
+    | { _type: 'GC', gc_eventlog: GCEvent[], stats: GCStats, side_tables?: SideTablesData, regions?: Regions, mark_bitmap?: MarkBitmap }
+    | { _type: 'RunFinalizers', gc_eventlog: GCEvent[] }
+    | { _type: 'Pin'; addr: number, pinned: boolean, gc_eventlog: GCEvent[] }
+
+export type BlockState = "Free" | "Recyclable" | "Unavailable";
+
+export interface Regions {
+    line_size: number;
+    block_size: number;
+    line_marks: boolean[];
+    block_states: BlockState[];
+    cursor: number;
+    limit: number;
+}
+
+export interface GCStats {
+    parallel_mark?: ParallelMarkStats;
+    card_table?: CardTableStats;
+    tlab?: TlabStats;
+    header_words?: number;
+}
+
+export interface TlabStats {
+    retired: number;
+    waste: number;
+}
+
+export interface CardTableStats {
+    dirtied: number;
+    scanned: number;
+}
+
+export interface ParallelMarkStats {
+    rounds: number;
+    workers: WorkerStats[];
+}
+
+export interface WorkerStats {
+    marked: number;
+    marked_words: number;
+    steals: number;
+    idle_rounds: number;
+}
+
+export interface MarkBitmap {
+    granularity: number;
+    bits: boolean[];
+}
+
+export interface SideTablesData {
+    mark_bitmap: boolean[];
+    block_size: number;
+    offset_table: number[];
+}
 
 interface Object {
     header: {};
     fields: Field[];
 }
 
-type Field = { ref: number } | { scalar: number };
+type Field = { ref: number }
+    | { weak: number | null }
+    | { ephemeron_key: number | null }
+    | { ephemeron_value: number | null }
+    | { scalar: number };
 
 type Value = number;
 
 
 export interface MemoryCell {
     status: CellStatus;
+    // raw tagged word in the cell, only sent in the word memory model
+    word?: number;
 }
 
 export type WSMsgRequest
@@ -54,21 +116,105 @@ export interface InfoBlockData {
     gc_type: string;
     alignment: number;
     heap_size: number;
+    alloc_policy: string;
     allocd_objects: number;
     free_memory: number;
+    header_words: number;
+    large_object_space?: LargeObjectSpaceInfo;
+    card_table?: CardTable;
+    size_classes?: SizeClassInfo[];
+    tlabs?: Tlabs;
+    buddy?: BuddyInfo;
+}
+
+export interface BuddyBlockInfo {
+    start: number;
+    size: number;
+    wasted: number;
+}
+
+export interface BuddyInfo {
+    free_blocks: [number, number][];
+    allocated_blocks: BuddyBlockInfo[];
+    wasted: number;
+}
+
+export interface Tlab {
+    start: number;
+    top: number;
+    end: number;
+}
+
+export interface Tlabs {
+    size: number;
+    buffers: Record<number, Tlab>;
+    retired: number;
+    waste: number;
+}
+
+export interface SizeClassInfo {
+    size: number;
+    free_cells: number;
+    used_cells: number;
+}
+
+export interface CardTable {
+    card_size: number;
+    cards: boolean[];
+    dirtied: number;
+}
+
+export interface LargeObjectSpaceInfo {
+    start: number;
+    page_size: number;
+    pages: number;
+    used_pages: number;
+    objects: number;
 }
 
 export const INFOBLOCK_DEFAULT: InfoBlockData = {
     gc_type: '',
     alignment: -1,
     heap_size: -1,
+    alloc_policy: '',
     allocd_objects: -1,
     free_memory: -1,
+    header_words: 0,
 }
 
 export type GCEvent =
     | { type: "GCPhase", msg: string }
-    | { type: "MarkObject", addr: number, size: number }
+    | { type: "MarkObject", addr: number, size: number, worker?: number }
+    | { type: "ShadeObject", addr: number, size: number }
     | { type: "FreeObject", addr: number, size: number }
     | { type: "MoveObject", from: number, to: number, size: number }
-    | { type: "UpdateFwdPtr", old: number, new: number };
+    | { type: "UpdateFwdPtr", old: number, new: number }
+    | { type: "ThreadPointer", field: number, obj: number }
+    | { type: "UnthreadPointer", field: number, new: number }
+    | { type: "CollectGeneration", generation: Generation }
+    | { type: "PromoteObject", from: number, to: number, size: number }
+    | { type: "IncRef", addr: number, count: number }
+    | { type: "DecRef", addr: number, count: number }
+    | { type: "ChangeColor", addr: number, color: Color }
+    | { type: "MarkLine", line: number }
+    | { type: "MoveSegmentPointer", pointer: SegmentPointer, from: number, to: number }
+    | { type: "ClearWeakRef", field: number, target: number }
+    | { type: "ResurrectObject", addr: number, size: number }
+    | { type: "EnqueueFinalizer", addr: number }
+    | { type: "RunFinalizer", addr: number }
+    | { type: "ReclaimFinalized", addr: number, size: number }
+    | { type: "EphemeronIteration", iteration: number, ready: number }
+    | { type: "ClearEphemeron", field: number }
+    | { type: "SkipPinned", addr: number, size: number }
+    | { type: "StealWork", thief: number, victim: number, addr: number }
+    | { type: "ScanCard", card: number, start: number, size: number }
+    | { type: "NewTlab", thread: number, start: number, end: number }
+    | { type: "RetireTlab", thread: number, start: number, end: number, waste: number }
+    | { type: "AllocationFailure", size: number }
+    | { type: "ResizeHeap", from: number, to: number };
+
+export type SegmentPointer = "Free" | "Bottom" | "Top" | "Scan";
+
+export type Generation = "Young" | "Old";
+
+export type Color = "Black" | "Gray" | "White" | "Purple";
@@ -1,49 +1,252 @@
+use serde::{Deserialize, Serialize};
+
 use crate::{
+    buddy::BuddyBlocks,
     error::VMError,
+    gc::{
+        immix, large_object_space, object_marker::set_marked, reference_counting::inc_ref,
+        treadmill, GCEvent,
+    },
     heap::Heap,
-    object::{ObjAddr, Object},
+    object::{Address, Field, ObjAddr, Object},
+    segregated_fits,
+    tlab::{self, Tlabs},
+    word,
 };
 
-pub struct Allocator {}
+/// Placement policy of free-list allocation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AllocPolicy {
+    /// The first block that fits, in address order.
+    #[default]
+    FirstFit,
+    /// The first block that fits at or after the end of the previous
+    /// allocation, wrapping around to the start of the heap.
+    NextFit,
+    /// The smallest block that fits.
+    BestFit,
+    /// The largest block, leaving the biggest remainder behind.
+    WorstFit,
+    /// Binary buddy blocks carved out of the free list, see `BuddyBlocks`.
+    /// Meant for collectors that do not move objects, e.g. MarkSweep.
+    Buddy,
+}
+
+pub struct Allocator {
+    policy: AllocPolicy,
+    /// Words of the thread-local allocation buffers, if objects are
+    /// bump-allocated through them.
+    tlab_size: Option<usize>,
+    /// Heap words reserved for the header in front of every object.
+    header_words: usize,
+}
 
 impl Allocator {
-    pub fn new() -> Self {
-        Allocator {}
+    pub fn new(policy: AllocPolicy, tlab_size: Option<usize>, header_words: usize) -> Self {
+        Allocator {
+            policy,
+            tlab_size,
+            header_words,
+        }
     }
 
+    /// Allocates `object` on the heap. Any collector work the allocation
+    /// involves, e.g. reference count updates, is logged to `eventlog`.
     pub fn allocate(
         &self,
         heap: &mut Heap,
         object: Object,
         is_root: bool,
+        eventlog: &mut Vec<GCEvent>,
+    ) -> Result<ObjAddr, VMError> {
+        self.allocate_on_thread(heap, object, is_root, 0, eventlog)
+    }
+
+    /// Allocates `object` on behalf of the simulated mutator `thread`,
+    /// which only matters when allocating through TLABs.
+    pub fn allocate_on_thread(
+        &self,
+        heap: &mut Heap,
+        object: Object,
+        is_root: bool,
+        thread: usize,
+        eventlog: &mut Vec<GCEvent>,
     ) -> Result<ObjAddr, VMError> {
+        let mut object = object;
+        object.header.words = self.header_words;
         let size = object.size();
+        if heap.word_memory {
+            object.fields.iter().try_for_each(word::check_field)?;
+        }
+
+        let addr = if heap
+            .large_objects
+            .as_ref()
+            .is_some_and(|los| los.is_large(size))
+        {
+            large_object_space::allocate(heap, size)
+        } else if heap.regions.is_some() {
+            self.bump_allocate(heap, size)
+        } else if heap.treadmill.is_some() {
+            self.treadmill_allocate(heap, size, eventlog)
+        } else if self.policy == AllocPolicy::Buddy {
+            self.buddy_allocate(heap, size)
+        } else if let Some(tlab_size) = self.tlab_size {
+            self.tlab_allocate(heap, size, thread, tlab_size, eventlog)
+        } else if let Some(class) = heap
+            .size_classes
+            .as_ref()
+            .and_then(|classes| classes.class_of(size))
+        {
+            segregated_fits::allocate(heap, class)
+        } else {
+            self.find_suitable_free_block(heap, size)
+        };
 
-        if let Some(aligned_start) = self.find_suitable_free_block(heap, size) {
+        if let Some(aligned_start) = addr {
+            if heap.ref_counting {
+                Self::init_ref_counts(heap, &mut object, is_root, eventlog);
+            }
             heap.objects.insert(aligned_start, object);
+            // Objects allocated during an incremental mark start out black
+            if heap.incremental_mark.is_some() {
+                set_marked(heap, aligned_start);
+            }
             if is_root {
                 heap.roots.insert(aligned_start);
             }
+            heap.write_object_words(aligned_start);
             Ok(aligned_start)
         } else {
             Err(VMError::AllocationError)
         }
     }
 
-    fn find_suitable_free_block(&self, heap: &mut Heap, size: usize) -> Option<ObjAddr> {
-        // Iterate over the blocks in the FreeList
-        for (block_start, block_size) in heap.free_list.to_vec() {
-            let aligned_start = heap.aligned_position(block_start);
-            let block_end = aligned_start + size;
-
-            // Check if the block can accommodate the required size after alignment
-            if block_end <= block_start + block_size {
-                // Update free_list
-                self.split_free_block(heap, block_start, block_size, aligned_start, block_end);
-                return Some(aligned_start);
+    /// Counts the root reference and the pointers an object is created with.
+    fn init_ref_counts(
+        heap: &mut Heap,
+        object: &mut Object,
+        is_root: bool,
+        eventlog: &mut Vec<GCEvent>,
+    ) {
+        object.header.rc = usize::from(is_root);
+        for field in &object.fields {
+            if let Field::Ref {
+                addr: Address::Ptr(target),
+            } = field
+            {
+                inc_ref(heap, *target, eventlog);
             }
         }
-        None
+    }
+
+    /// Mark-region heaps are allocated into by bumping through holes of
+    /// free lines; the free list only keeps track of the free memory.
+    /// Objects larger than a line that fit no hole, e.g. ones larger than
+    /// a block, overflow into the first free range that fits them.
+    fn bump_allocate(&self, heap: &mut Heap, size: usize) -> Option<ObjAddr> {
+        if let Some(start) = immix::find_room(heap, size) {
+            let start = self.claim(heap, start, size)?;
+            immix::bump(heap, start + size);
+            return Some(start);
+        }
+        if size <= heap.regions.as_ref()?.line_size {
+            return None;
+        }
+        self.find_suitable_free_block(heap, size)
+    }
+
+    /// Treadmill heaps are allocated into cell by cell. The object takes
+    /// its whole cell, whatever it does not fill is not free either.
+    fn treadmill_allocate(
+        &self,
+        heap: &mut Heap,
+        size: usize,
+        eventlog: &mut Vec<GCEvent>,
+    ) -> Option<ObjAddr> {
+        let start = treadmill::allocate(heap, size, eventlog)?;
+        let cell_size = heap.treadmill.as_ref()?.cell_size;
+        self.claim(heap, start, cell_size)
+    }
+
+    /// The buddy system takes over the free list on the first allocation.
+    fn buddy_allocate(&self, heap: &mut Heap, size: usize) -> Option<ObjAddr> {
+        if heap.buddy.is_none() {
+            heap.buddy = Some(BuddyBlocks::reserve(heap));
+        }
+        let alignment = heap.alignment;
+        heap.buddy.as_mut()?.allocate(size, alignment)
+    }
+
+    /// Bumps through the buffer of `thread`. A full buffer is retired and
+    /// replaced by a fresh one carved out of the free list.
+    fn tlab_allocate(
+        &self,
+        heap: &mut Heap,
+        size: usize,
+        thread: usize,
+        tlab_size: usize,
+        eventlog: &mut Vec<GCEvent>,
+    ) -> Option<ObjAddr> {
+        let tlab_size = heap.tlabs.get_or_insert_with(|| Tlabs::new(tlab_size)).size;
+        if let Some(addr) = tlab::bump(heap, thread, size) {
+            return Some(addr);
+        }
+        // Objects larger than a buffer are allocated from the free list directly
+        if size > tlab_size {
+            return self.find_suitable_free_block(heap, size);
+        }
+        tlab::retire(heap, thread, eventlog);
+        match self.find_suitable_free_block(heap, tlab_size) {
+            Some(start) => {
+                tlab::install(heap, thread, start, start + tlab_size, eventlog);
+                tlab::bump(heap, thread, size)
+            }
+            // No room for a whole buffer, fall back to the free list
+            None => self.find_suitable_free_block(heap, size),
+        }
+    }
+
+    /// Removes `[start, start + size)` from the free list.
+    fn claim(&self, heap: &mut Heap, start: ObjAddr, size: usize) -> Option<ObjAddr> {
+        let (block_start, block_size) =
+            heap.free_list
+                .to_vec()
+                .into_iter()
+                .find(|(block_start, block_size)| {
+                    *block_start <= start && start + size <= block_start + block_size
+                })?;
+        self.split_free_block(heap, block_start, block_size, start, start + size);
+        Some(start)
+    }
+
+    fn find_suitable_free_block(&self, heap: &mut Heap, size: usize) -> Option<ObjAddr> {
+        let mut blocks = heap.free_list.to_vec();
+        if self.policy == AllocPolicy::NextFit {
+            // Start the search at the roving pointer and wrap around
+            let rover = heap.next_fit.unwrap_or(0);
+            let first = blocks.partition_point(|(block_start, _)| *block_start < rover);
+            blocks.rotate_left(first);
+        }
+
+        // Blocks that can accommodate the required size after alignment
+        let mut fits = blocks.into_iter().filter(|(block_start, block_size)| {
+            heap.aligned_position(*block_start) + size <= block_start + block_size
+        });
+        let (block_start, block_size) = match self.policy {
+            AllocPolicy::FirstFit | AllocPolicy::NextFit | AllocPolicy::Buddy => fits.next(),
+            AllocPolicy::BestFit => fits.min_by_key(|(_, block_size)| *block_size),
+            // The lowest address wins a tie, like it does for best-fit
+            AllocPolicy::WorstFit => fits.rev().max_by_key(|(_, block_size)| *block_size),
+        }?;
+
+        let aligned_start = heap.aligned_position(block_start);
+        let block_end = aligned_start + size;
+        self.split_free_block(heap, block_start, block_size, aligned_start, block_end);
+        if self.policy == AllocPolicy::NextFit {
+            heap.next_fit = Some(block_end);
+        }
+        Some(aligned_start)
     }
 
     fn split_free_block(
@@ -75,12 +278,12 @@ impl Allocator {
 
 impl Default for Allocator {
     fn default() -> Self {
-        Self::new()
+        Self::new(AllocPolicy::default(), None, 0)
     }
 }
 #[cfg(test)]
 mod tests {
-    use std::collections::{BTreeMap, BTreeSet};
+    use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
     use crate::{free_list::FreeList, heap::MemoryCell, object::Field};
 
@@ -93,13 +296,28 @@ mod tests {
             memory: vec![MemoryCell::free(); 10], // Assuming a size of 10 for simplicity
             free_list: FreeList::new(free_list),
             alignment,
+            generations: None,
+            ref_counting: false,
+            candidate_roots: BTreeSet::new(),
+            incremental_mark: None,
+            regions: None,
+            treadmill: None,
+            finalization_queue: VecDeque::new(),
+            large_objects: None,
+            mark_bitmap: None,
+            card_table: None,
+            next_fit: None,
+            size_classes: None,
+            tlabs: None,
+            buddy: None,
+            word_memory: false,
         }
     }
 
     #[test]
     fn test_find_suitable_free_block_with_sufficient_space() {
         let mut heap = create_heap_with_free_list(2, vec![(0, 4)]);
-        let allocator = Allocator {};
+        let allocator = Allocator::default();
 
         let result = allocator.find_suitable_free_block(&mut heap, 3);
         assert_eq!(result, Some(0));
@@ -109,7 +327,7 @@ mod tests {
     #[test]
     fn test_find_suitable_free_block_first_block() {
         let mut heap = create_heap_with_free_list(2, vec![(2, 2), (8, 2)]);
-        let allocator = Allocator {};
+        let allocator = Allocator::default();
 
         let result = allocator.find_suitable_free_block(&mut heap, 2);
         assert_eq!(result, Some(2));
@@ -119,7 +337,7 @@ mod tests {
     #[test]
     fn test_find_suitable_free_block_without_sufficient_space() {
         let mut heap = create_heap_with_free_list(2, vec![(0, 2)]);
-        let allocator = Allocator {};
+        let allocator = Allocator::default();
 
         let result = allocator.find_suitable_free_block(&mut heap, 3);
         assert_eq!(result, None);
@@ -129,19 +347,66 @@ mod tests {
     #[test]
     fn test_find_suitable_free_block_with_alignment() {
         let mut heap = create_heap_with_free_list(2, vec![(1, 4)]);
-        let allocator = Allocator {};
+        let allocator = Allocator::default();
 
         let result = allocator.find_suitable_free_block(&mut heap, 3);
         assert_eq!(result, Some(2)); // Starts at 2 because of alignment
         assert_eq!(heap.free_list.to_vec(), vec![(1, 1)]); // Only 1 cell before the allocated space
     }
 
+    #[test]
+    fn test_next_fit_resumes_at_rover() {
+        let mut heap = create_heap_with_free_list(0, vec![(0, 2), (4, 4)]);
+        heap.next_fit = Some(4);
+        let allocator = Allocator::new(AllocPolicy::NextFit, None, 0);
+
+        // First-fit would take the block at 0
+        assert_eq!(allocator.find_suitable_free_block(&mut heap, 2), Some(4));
+        assert_eq!(heap.next_fit, Some(6));
+        assert_eq!(allocator.find_suitable_free_block(&mut heap, 2), Some(6));
+        // Nothing is left after the rover, the search wraps around
+        assert_eq!(allocator.find_suitable_free_block(&mut heap, 2), Some(0));
+        assert_eq!(heap.next_fit, Some(2));
+        assert!(heap.free_list.to_vec().is_empty());
+    }
+
+    #[test]
+    fn test_best_fit_takes_smallest_block() {
+        let mut heap = create_heap_with_free_list(0, vec![(0, 4), (5, 2), (8, 2)]);
+        let allocator = Allocator::new(AllocPolicy::BestFit, None, 0);
+
+        let result = allocator.find_suitable_free_block(&mut heap, 2);
+        assert_eq!(result, Some(5)); // The lowest of the two exact fits
+        assert_eq!(heap.free_list.to_vec(), vec![(0, 4), (8, 2)]);
+    }
+
+    #[test]
+    fn test_worst_fit_takes_largest_block() {
+        let mut heap = create_heap_with_free_list(0, vec![(0, 2), (3, 4), (8, 4)]);
+        let allocator = Allocator::new(AllocPolicy::WorstFit, None, 0);
+
+        let result = allocator.find_suitable_free_block(&mut heap, 2);
+        assert_eq!(result, Some(3)); // The lowest of the two largest blocks
+        assert_eq!(heap.free_list.to_vec(), vec![(0, 2), (5, 2), (8, 4)]);
+    }
+
+    #[test]
+    fn test_best_fit_with_alignment() {
+        // (7, 2) is the smallest block but too small once aligned
+        let mut heap = create_heap_with_free_list(2, vec![(0, 6), (7, 2)]);
+        let allocator = Allocator::new(AllocPolicy::BestFit, None, 0);
+
+        let result = allocator.find_suitable_free_block(&mut heap, 2);
+        assert_eq!(result, Some(0));
+        assert_eq!(heap.free_list.to_vec(), vec![(2, 4), (7, 2)]);
+    }
+
     #[test]
     fn test_split_free_block_no_remainder() {
         let mut heap = Heap::new(8, 0);
         heap.free_list = free_list![(0, 8)];
 
-        let allocator = Allocator::new();
+        let allocator = Allocator::default();
         allocator.split_free_block(&mut heap, 0, 8, 0, 4);
 
         assert_eq!(heap.free_list.to_vec(), vec![(4, 4)]);
@@ -152,7 +417,7 @@ mod tests {
         let mut heap = Heap::new(10, 4);
         heap.free_list = free_list![(0, 8)];
 
-        let allocator = Allocator::new();
+        let allocator = Allocator::default();
         allocator.split_free_block(&mut heap, 0, 8, 2, 6);
 
         assert_eq!(heap.free_list.to_vec(), vec![(0, 2), (6, 2)]);
@@ -163,7 +428,7 @@ mod tests {
         let mut heap = Heap::new(10, 4);
         heap.free_list = free_list![(0, 8)];
 
-        let allocator = Allocator::new();
+        let allocator = Allocator::default();
         allocator.split_free_block(&mut heap, 0, 8, 0, 6);
 
         assert_eq!(heap.free_list.to_vec(), vec![(6, 2)]);
@@ -174,7 +439,7 @@ mod tests {
         let mut heap = Heap::new(10, 4);
         heap.free_list = free_list![(0, 8)];
 
-        let allocator = Allocator::new();
+        let allocator = Allocator::default();
         allocator.split_free_block(&mut heap, 0, 8, 2, 6);
 
         assert_eq!(heap.free_list.to_vec(), vec![(0, 2), (6, 2)]);
@@ -185,7 +450,7 @@ mod tests {
         let mut heap = Heap::new(8, 4);
         heap.free_list = free_list![(0, 4), (4, 4)];
 
-        let allocator = Allocator::new();
+        let allocator = Allocator::default();
         allocator.split_free_block(&mut heap, 8, 4, 8, 12);
 
         assert_eq!(heap.free_list.to_vec(), vec![(0, 4), (4, 4)]); // Free list remains unchanged.
@@ -194,14 +459,14 @@ mod tests {
     #[test]
     fn test_allocate_with_sufficient_space() {
         let mut heap = create_heap_with_free_list(2, vec![(0, 4)]);
-        let allocator = Allocator {};
+        let allocator = Allocator::default();
         let object = Object::new(vec![
             Field::new_scalar(1),
             Field::new_scalar(2),
             Field::new_scalar(3),
         ]);
 
-        let result = allocator.allocate(&mut heap, object, true);
+        let result = allocator.allocate(&mut heap, object, true, &mut vec![]);
         assert!(result.is_ok());
         assert_eq!(heap.objects.len(), 1); // The object should be added to `heap.objects`.
         assert_eq!(heap.roots.len(), 1); // Since it's marked as root, it should be added to `heap.roots`.
@@ -211,14 +476,14 @@ mod tests {
     #[test]
     fn test_allocate_without_sufficient_space() {
         let mut heap = create_heap_with_free_list(2, vec![(0, 2)]);
-        let allocator = Allocator {};
+        let allocator = Allocator::default();
         let object = Object::new(vec![
             Field::new_scalar(1),
             Field::new_scalar(2),
             Field::new_scalar(3),
         ]);
 
-        let result = allocator.allocate(&mut heap, object, true);
+        let result = allocator.allocate(&mut heap, object, true, &mut vec![]);
         assert!(result.is_err()); // Allocation should fail.
         assert_eq!(heap.objects.len(), 0); // No object should be added.
         assert_eq!(heap.free_list.to_vec(), vec![(0, 2)]); // Free list should remain unchanged.
@@ -227,7 +492,7 @@ mod tests {
     #[test]
     fn test_allocate_multiple_objects() {
         let mut heap = create_heap_with_free_list(2, vec![(0, 10)]);
-        let allocator = Allocator {};
+        let allocator = Allocator::default();
 
         let object1 = Object::new(vec![Field::new_scalar(1), Field::new_scalar(2)]);
         let object2 = Object::new(vec![
@@ -237,9 +502,15 @@ mod tests {
         ]);
         let object3 = Object::new(vec![Field::new_scalar(6), Field::new_scalar(7)]);
 
-        allocator.allocate(&mut heap, object1, true).unwrap();
-        allocator.allocate(&mut heap, object2, true).unwrap();
-        allocator.allocate(&mut heap, object3, false).unwrap();
+        allocator
+            .allocate(&mut heap, object1, true, &mut vec![])
+            .unwrap();
+        allocator
+            .allocate(&mut heap, object2, true, &mut vec![])
+            .unwrap();
+        allocator
+            .allocate(&mut heap, object3, false, &mut vec![])
+            .unwrap();
 
         assert_eq!(heap.objects.len(), 3); // Three objects should be added.
         assert_eq!(heap.roots.len(), 2); // Only two objects were marked as roots.
@@ -249,15 +520,19 @@ mod tests {
     #[test]
     fn test_allocate_after_deallocate() {
         let mut heap = create_heap_with_free_list(2, vec![(0, 10)]);
-        let allocator = Allocator {};
+        let allocator = Allocator::default();
 
         let object1 = Object::new(vec![Field::new_scalar(1), Field::new_scalar(2)]);
-        let addr1 = allocator.allocate(&mut heap, object1, true).unwrap();
+        let addr1 = allocator
+            .allocate(&mut heap, object1, true, &mut vec![])
+            .unwrap();
 
         heap.free_object(addr1).unwrap();
 
         let object2 = Object::new(vec![Field::new_scalar(3), Field::new_scalar(4)]);
-        let addr2 = allocator.allocate(&mut heap, object2, false).unwrap();
+        let addr2 = allocator
+            .allocate(&mut heap, object2, false, &mut vec![])
+            .unwrap();
 
         assert_eq!(addr1, addr2); // Should allocate in the same spot as the deallocated object.
         assert_eq!(heap.objects.len(), 1);
@@ -267,12 +542,14 @@ mod tests {
     #[test]
     fn test_calc_memory_after_allocate() {
         let mut heap = create_heap_with_free_list(2, vec![(0, 10)]);
-        let allocator = Allocator {};
+        let allocator = Allocator::default();
 
         assert_eq!(heap.calc_free_memory(), 10);
 
         let object1 = Object::new(vec![Field::new_scalar(1), Field::new_scalar(2)]);
-        let addr1 = allocator.allocate(&mut heap, object1, true).unwrap();
+        let addr1 = allocator
+            .allocate(&mut heap, object1, true, &mut vec![])
+            .unwrap();
 
         assert_eq!(heap.calc_free_memory(), 8);
 
@@ -281,17 +558,206 @@ mod tests {
         assert_eq!(heap.calc_free_memory(), 10);
     }
 
+    #[test]
+    fn test_allocate_large_object() {
+        let mut heap = Heap::new(32, 1);
+        crate::gc::large_object_space::reserve(&mut heap, Some(4));
+        let allocator = Allocator::default();
+
+        let small = Object::new(vec![Field::new_scalar(1); 3]);
+        let large = Object::new(vec![Field::new_scalar(2); 5]);
+        assert_eq!(
+            allocator.allocate(&mut heap, small, true, &mut vec![]),
+            Ok(0)
+        );
+        // Five words take both pages of the large object space
+        assert_eq!(
+            allocator.allocate(&mut heap, large.clone(), true, &mut vec![]),
+            Ok(24)
+        );
+        assert_eq!(heap.calc_free_memory(), 21);
+        assert_eq!(
+            allocator.allocate(&mut heap, large.clone(), true, &mut vec![]),
+            Err(VMError::AllocationError)
+        );
+
+        heap.free_object(24).unwrap();
+        assert_eq!(heap.calc_free_memory(), 21);
+        assert_eq!(
+            allocator.allocate(&mut heap, large, true, &mut vec![]),
+            Ok(24)
+        );
+    }
+
+    #[test]
+    fn test_overflow_allocate_in_regions() {
+        let mut heap = Heap::new(32, 1);
+        heap.regions = Some(crate::gc::immix::Regions::new(32));
+        let allocator = Allocator::default();
+
+        let small = Object::new(vec![Field::new_scalar(1); 2]);
+        let large = Object::new(vec![Field::new_scalar(2); 20]);
+        assert_eq!(
+            allocator.allocate(&mut heap, small.clone(), true, &mut vec![]),
+            Ok(0)
+        );
+        // Larger than a block, so it fits no hole and spans both blocks
+        assert_eq!(
+            allocator.allocate(&mut heap, large, true, &mut vec![]),
+            Ok(2)
+        );
+        // The hole of the second block starts behind the overflowed object
+        assert_eq!(
+            allocator.allocate(&mut heap, small, true, &mut vec![]),
+            Ok(22)
+        );
+        assert_eq!(heap.calc_free_memory(), 8);
+    }
+
+    #[test]
+    fn test_allocate_from_size_classes() {
+        let mut heap = Heap::new(16, 0);
+        heap.size_classes = Some(crate::segregated_fits::SizeClasses::new(&[1, 2]));
+        let allocator = Allocator::default();
+
+        let one = Object::new(vec![Field::new_scalar(1)]);
+        let two = Object::new(vec![Field::new_scalar(2); 2]);
+        let three = Object::new(vec![Field::new_scalar(3); 3]);
+        // Each class is refilled with four cells from the general pool
+        assert_eq!(
+            allocator.allocate(&mut heap, one.clone(), true, &mut vec![]),
+            Ok(0)
+        );
+        assert_eq!(allocator.allocate(&mut heap, two, true, &mut vec![]), Ok(4));
+        // Too large for any class
+        assert_eq!(
+            allocator.allocate(&mut heap, three, true, &mut vec![]),
+            Ok(12)
+        );
+        assert_eq!(heap.free_list.to_vec(), vec![(15, 1)]);
+        assert_eq!(heap.calc_free_memory(), 10);
+
+        // A freed cell goes back to its class and is handed out next
+        heap.free_object(0).unwrap();
+        assert_eq!(heap.free_list.to_vec(), vec![(15, 1)]);
+        assert_eq!(allocator.allocate(&mut heap, one, true, &mut vec![]), Ok(0));
+    }
+
+    #[test]
+    fn test_buddy_allocation() {
+        let mut heap = Heap::new(16, 0);
+        let allocator = Allocator::new(AllocPolicy::Buddy, None, 0);
+        let allocate = |heap: &mut Heap, size: usize| {
+            let object = Object::new(vec![Field::new_scalar(size); size]);
+            allocator.allocate(heap, object, true, &mut vec![])
+        };
+
+        // 16 is split into 8 + 8 and then 4 + 4 for a 3-word object
+        assert_eq!(allocate(&mut heap, 3), Ok(0));
+        assert_eq!(allocate(&mut heap, 5), Ok(8));
+        assert_eq!(allocate(&mut heap, 2), Ok(4));
+        assert_eq!(allocate(&mut heap, 9), Err(VMError::AllocationError));
+        let buddy = heap.buddy.as_ref().unwrap();
+        assert_eq!(buddy.free_blocks(), vec![(6, 2)]);
+        assert!(heap.free_list.to_vec().is_empty());
+        assert_eq!(heap.calc_free_memory(), 2);
+
+        // The block at 0 waits for its buddy at 4, which coalesces with
+        // the free block at 6 first
+        heap.free_object(0).unwrap();
+        assert_eq!(
+            heap.buddy.as_ref().unwrap().free_blocks(),
+            vec![(0, 4), (6, 2)]
+        );
+        heap.free_object(4).unwrap();
+        assert_eq!(heap.buddy.as_ref().unwrap().free_blocks(), vec![(0, 8)]);
+        heap.free_object(8).unwrap();
+        assert_eq!(heap.buddy.as_ref().unwrap().free_blocks(), vec![(0, 16)]);
+    }
+
+    #[test]
+    fn test_buddy_allocation_non_power_of_two_heap() {
+        let mut heap = Heap::new(12, 2);
+        let allocator = Allocator::new(AllocPolicy::Buddy, None, 0);
+        let object = Object::new(vec![Field::new_scalar(1)]);
+        // Blocks are never smaller than the alignment
+        assert_eq!(
+            allocator.allocate(&mut heap, object.clone(), true, &mut vec![]),
+            Ok(8)
+        );
+        let buddy = heap.buddy.as_ref().unwrap();
+        assert_eq!(buddy.free_blocks(), vec![(0, 8), (10, 2)]);
+        heap.free_object(8).unwrap();
+        // The block at 8 has no buddy at 12 to merge with
+        assert_eq!(
+            heap.buddy.as_ref().unwrap().free_blocks(),
+            vec![(0, 8), (8, 4)]
+        );
+    }
+
+    #[test]
+    fn test_allocate_with_headers() {
+        let mut heap = Heap::new(16, 2);
+        let allocator = Allocator::new(AllocPolicy::FirstFit, None, 1);
+        let object = Object::new(vec![Field::new_scalar(1), Field::new_scalar(2)]);
+
+        // Every object takes up a header word in front of its fields
+        assert_eq!(
+            allocator.allocate(&mut heap, object.clone(), true, &mut vec![]),
+            Ok(0)
+        );
+        assert_eq!(
+            allocator.allocate(&mut heap, object, true, &mut vec![]),
+            Ok(4)
+        );
+        assert_eq!(heap.free_list.to_vec(), vec![(3, 1), (7, 9)]);
+        assert_eq!(heap.calc_free_memory(), 10);
+        assert_eq!(heap.header_overhead(), 2);
+
+        assert!(heap.field_at(4).is_none());
+        assert!(matches!(heap.field_at(5), Some(Field::Scalar { value: 1 })));
+    }
+
+    #[test]
+    fn test_allocate_through_tlabs() {
+        let mut heap = Heap::new(16, 0);
+        let allocator = Allocator::new(AllocPolicy::FirstFit, Some(4), 0);
+        let mut allocate = |size: usize, thread: usize| {
+            let object = Object::new(vec![Field::new_scalar(thread); size]);
+            allocator.allocate_on_thread(&mut heap, object, true, thread, &mut vec![])
+        };
+
+        // Every thread bumps through a buffer of its own
+        assert_eq!(allocate(2, 0), Ok(0));
+        assert_eq!(allocate(1, 1), Ok(4));
+        assert_eq!(allocate(2, 0), Ok(2));
+        // The buffer of thread 1 is retired with three words left unused,
+        // they stay off the free list as filler
+        assert_eq!(allocate(4, 1), Ok(8));
+
+        let tlabs = heap.tlabs.as_ref().unwrap();
+        assert_eq!(tlabs.retired, 1);
+        assert_eq!(tlabs.waste, 3);
+        assert_eq!(tlabs.fillers, vec![(5, 3)]);
+        assert_eq!(heap.free_list.to_vec(), vec![(12, 4)]);
+
+        // A collection hands the filler back
+        tlab::retire_all(&mut heap);
+        assert!(heap.tlabs.as_ref().unwrap().fillers.is_empty());
+        assert_eq!(heap.free_list.to_vec(), vec![(5, 3), (12, 4)]);
+    }
+
     #[test]
     fn test_free_object() {
         let mut heap = create_heap_with_free_list(2, vec![(0, 10)]);
-        let allocator = Allocator {};
+        let allocator = Allocator::default();
         let object = Object::new(vec![
             Field::new_scalar(1),
             Field::new_scalar(2),
             Field::new_scalar(3),
         ]);
 
-        let result = allocator.allocate(&mut heap, object, true);
+        let result = allocator.allocate(&mut heap, object, true, &mut vec![]);
         assert_eq!(result, Ok(0));
         assert_eq!(heap.calc_free_memory(), 7);
         assert_eq!(heap.objects.len(), 1);
@@ -0,0 +1,117 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{heap::Heap, object::ObjAddr};
+
+/// Binary buddy system. Every block is a power of two words long and
+/// starts at a multiple of its size, so the buddy of a block is found by
+/// flipping a single address bit. Requests are rounded up to a power of
+/// two, larger blocks are split in halves until one fits, and a freed
+/// block is merged with its buddy for as long as the buddy is free too.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BuddyBlocks {
+    /// Start addresses of the free blocks of each order,
+    /// order `k` holding blocks of `2^k` words.
+    pub free: Vec<BTreeSet<ObjAddr>>,
+    /// Size of the block each allocated object sits in.
+    pub allocated: BTreeMap<ObjAddr, usize>,
+}
+
+/// Size of the block an object of `size` words is given. Blocks at least
+/// as large as the alignment keep objects aligned.
+pub fn block_size(size: usize, alignment: usize) -> usize {
+    size.max(alignment).max(1).next_power_of_two()
+}
+
+impl BuddyBlocks {
+    /// Takes over the free list of `heap`. Its blocks are split into the
+    /// largest naturally aligned power-of-two blocks they hold.
+    pub fn reserve(heap: &mut Heap) -> Self {
+        let mut buddy = BuddyBlocks {
+            free: vec![],
+            allocated: BTreeMap::new(),
+        };
+        for (block_start, block_size) in heap.free_list.to_vec() {
+            heap.free_list.remove(block_start);
+            let block_end = block_start + block_size;
+            let mut addr = block_start;
+            while addr < block_end {
+                let mut order = (block_end - addr).ilog2();
+                if addr > 0 {
+                    order = order.min(addr.trailing_zeros());
+                }
+                buddy.insert_free(addr, 1 << order);
+                addr += 1 << order;
+            }
+        }
+        buddy
+    }
+
+    /// Takes a block that fits `size` words, splitting a larger one if no
+    /// block of the right size is free.
+    pub fn allocate(&mut self, size: usize, alignment: usize) -> Option<ObjAddr> {
+        let block_size = block_size(size, alignment);
+        let order = block_size.trailing_zeros() as usize;
+        let (addr, mut split_order) =
+            (order..self.free.len()).find_map(|k| self.free[k].first().map(|addr| (*addr, k)))?;
+        self.free[split_order].remove(&addr);
+        // Keep the lower half and give the upper one back, until it fits
+        while split_order > order {
+            split_order -= 1;
+            self.insert_free(addr + (1 << split_order), 1 << split_order);
+        }
+        self.allocated.insert(addr, block_size);
+        Some(addr)
+    }
+
+    /// Frees the block of the object at `addr` and coalesces it with its
+    /// buddy as far as possible. Two free buddies always make up a free
+    /// block of twice the size. Returns false if `addr` is not a block.
+    pub fn release(&mut self, addr: ObjAddr) -> bool {
+        let Some(mut size) = self.allocated.remove(&addr) else {
+            return false;
+        };
+        let mut addr = addr;
+        loop {
+            let buddy = addr ^ size;
+            let order = size.trailing_zeros() as usize;
+            if !self
+                .free
+                .get_mut(order)
+                .is_some_and(|free| free.remove(&buddy))
+            {
+                break;
+            }
+            addr = addr.min(buddy);
+            size *= 2;
+        }
+        self.insert_free(addr, size);
+        true
+    }
+
+    /// Free blocks as `(start, size)`, in address order.
+    pub fn free_blocks(&self) -> Vec<(ObjAddr, usize)> {
+        let mut blocks: Vec<(ObjAddr, usize)> = self
+            .free
+            .iter()
+            .enumerate()
+            .flat_map(|(order, free)| free.iter().map(move |addr| (*addr, 1 << order)))
+            .collect();
+        blocks.sort_unstable();
+        blocks
+    }
+
+    /// Words held by free blocks.
+    pub fn free_words(&self) -> usize {
+        self.free_blocks().iter().map(|(_, size)| size).sum()
+    }
+
+    fn insert_free(&mut self, addr: ObjAddr, size: usize) {
+        let order = size.trailing_zeros() as usize;
+        if self.free.len() <= order {
+            self.free.resize(order + 1, BTreeSet::new());
+        }
+        self.free[order].insert(addr);
+    }
+}
@@ -3,9 +3,12 @@ use std::fmt;
 #[derive(Debug, PartialEq, Eq)]
 pub enum VMError {
     AllocationError,
+    OutOfMemory,
     DeallocationError,
     SegmentationFault,
     NullPointerException(String),
+    CyclicReference(String),
+    WordOverflow(String),
     GCError,
     UnknownError,
 }
@@ -14,11 +17,14 @@ impl fmt::Display for VMError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             VMError::AllocationError => write!(f, "Allocation error"),
+            VMError::OutOfMemory => write!(f, "Out of memory"),
             VMError::DeallocationError => write!(f, "Deallocation error"),
             VMError::SegmentationFault => write!(f, "Segmentation fault"),
             VMError::NullPointerException(detail) => {
                 write!(f, "Null pointer exception: {detail}")
             }
+            VMError::CyclicReference(detail) => write!(f, "Cyclic reference: {detail}"),
+            VMError::WordOverflow(detail) => write!(f, "Word overflow: {detail}"),
             VMError::GCError => write!(f, "Garbage collector error"),
             VMError::UnknownError => write!(f, "UnknownError error"),
         }
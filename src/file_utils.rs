@@ -17,6 +17,8 @@ use crate::{heap::Heap, program::Program};
 pub enum CustomError {
     Json(SerdeJsonError),
     Yaml(SerdeYamlError),
+    /// Runtime configuration the collector cannot run with.
+    Config(String),
 }
 
 impl Error for CustomError {
@@ -24,6 +26,7 @@ impl Error for CustomError {
         match self {
             CustomError::Json(e) => Some(e),
             CustomError::Yaml(e) => Some(e),
+            CustomError::Config(_) => None,
         }
     }
 }
@@ -33,6 +36,7 @@ impl fmt::Display for CustomError {
         match self {
             CustomError::Json(e) => write!(f, "JSON error: {e}"),
             CustomError::Yaml(e) => write!(f, "YAML error: {e}"),
+            CustomError::Config(e) => write!(f, "Config error: {e}"),
         }
     }
 }
@@ -84,6 +88,7 @@ fn load_program_from_file(filename: &str) -> Result<(Program, ProgramRuntimeConf
 
     let config: ProgramRuntimeConfig =
         serde_yaml::from_value(value["rts_config"].clone()).map_err(CustomError::from)?;
+    config.validate().map_err(CustomError::Config)?;
     let program: Program =
         serde_yaml::from_value(value["program"].clone()).map_err(CustomError::from)?;
 
@@ -102,9 +107,9 @@ lazy_static! {
     pub static ref CURRENT_DIR: PathBuf = env::current_dir().unwrap();
 }
 
-pub fn load_program(file_name: &str) -> (Program, ProgramRuntimeConfig) {
+pub fn load_program(file_name: &str) -> Result<(Program, ProgramRuntimeConfig), CustomError> {
     let path = format!("{}/tests/{file_name}.yaml", CURRENT_DIR.display());
-    load_program_from_file(path.as_str()).unwrap()
+    load_program_from_file(path.as_str())
 }
 
 pub fn load_heap_snapshot(file_name: &str) -> Heap {
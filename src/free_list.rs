@@ -50,7 +50,7 @@ impl FreeList {
         Self { inner }
     }
 
-    pub fn iter(&self) -> FreeListIter {
+    pub fn iter(&self) -> FreeListIter<'_> {
         FreeListIter {
             inner_iter: self.inner.iter(),
         }
@@ -83,7 +83,7 @@ impl FreeList {
                 if start2 <= end1 {
                     // Overlapping or adjacent blocks found
                     // Update the length of the current block
-                    *self.inner.get_mut(&start1).unwrap() = len1 + len2 + start2 - end1;
+                    *self.inner.get_mut(&start1).unwrap() = end1.max(start2 + len2) - start1;
                     // Remove the next block
                     self.inner.remove(&start2);
                 } else {
@@ -141,6 +141,14 @@ mod tests {
         assert_eq!(free_list.to_vec(), vec![(1, 9)]);
     }
 
+    #[test]
+    fn test_merge_contained_range() {
+        let mut free_list = FreeList::new(vec![(8, 8), (10, 1)]);
+        free_list.merge_adjacent_blocks();
+
+        assert_eq!(free_list.to_vec(), vec![(8, 8)]);
+    }
+
     #[test]
     fn test_merge_multiple_ranges_collapse_all() {
         let mut free_list = FreeList::new(vec![(1, 3), (4, 7), (10, 13), (12, 15)]);
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    heap::Heap,
+    object::{Address, Field},
+};
+
+use super::GCEvent;
+
+/// Coarse remembered set over `Heap::memory`. The heap is split into cards
+/// of `card_size` words and the write barrier dirties the card of every
+/// slot a pointer is stored into. A collector then scans the dirty cards
+/// for interesting pointers instead of the whole heap.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CardTable {
+    pub card_size: usize,
+    /// Dirty flag of each card.
+    pub cards: Vec<bool>,
+    /// Cards the mutator dirtied since the last collection.
+    pub dirtied: usize,
+}
+
+impl CardTable {
+    pub fn new(heap_size: usize, card_size: usize) -> Self {
+        let card_size = card_size.max(1);
+        CardTable {
+            card_size,
+            cards: vec![false; heap_size.div_ceil(card_size)],
+            dirtied: 0,
+        }
+    }
+
+    pub fn card_of(&self, addr: usize) -> usize {
+        addr / self.card_size
+    }
+
+    /// Card marking barrier, invoked on every pointer store into `slot`.
+    pub fn dirty(&mut self, slot: usize) {
+        let card = self.card_of(slot);
+        if let Some(dirty) = self.cards.get_mut(card) {
+            if !*dirty {
+                *dirty = true;
+                self.dirtied += 1;
+            }
+        }
+    }
+
+    /// Cleans every card and starts counting dirtied cards anew.
+    pub fn clear(&mut self) {
+        self.cards.fill(false);
+        self.dirtied = 0;
+    }
+}
+
+/// Scans the dirty cards below `end` and cleans them. Returns the slots
+/// holding pointers found on those cards, along with the number of cards
+/// scanned. Objects straddling a card boundary are only scanned for the
+/// part that lies on the dirty card.
+pub fn scan_dirty_cards(
+    heap: &mut Heap,
+    eventlog: &mut Vec<GCEvent>,
+    end: usize,
+) -> (Vec<usize>, usize) {
+    let Some(table) = heap.card_table.as_ref() else {
+        return (vec![], 0);
+    };
+    let card_size = table.card_size;
+    let dirty: Vec<usize> = table
+        .cards
+        .iter()
+        .enumerate()
+        .filter(|(card, dirty)| **dirty && card * card_size < end)
+        .map(|(card, _)| card)
+        .collect();
+
+    let mut slots = vec![];
+    for card in &dirty {
+        let start = card * card_size;
+        let card_end = (start + card_size).min(end);
+        eventlog.push(GCEvent::ScanCard {
+            card: *card,
+            start,
+            size: card_end - start,
+        });
+        slots.extend((start..card_end).filter(|slot| {
+            matches!(
+                heap.field_at(*slot),
+                Some(Field::Ref {
+                    addr: Address::Ptr(_)
+                })
+            )
+        }));
+        heap.card_table.as_mut().unwrap().cards[*card] = false;
+    }
+    (slots, dirty.len())
+}
@@ -1,7 +1,29 @@
-use crate::{gc::GCEvent, heap::Heap, object::ObjAddr};
+use crate::{
+    free_list::FreeList,
+    gc::{ephemeron, GCEvent},
+    heap::Heap,
+    object::{Address, Field, ObjAddr},
+};
+
+/// Copies the object at `from` to `to` and leaves a forwarding
+/// address in the header of the original. In the word memory model the
+/// words are copied cell by cell, and the forwarding address is written
+/// over the original's header word.
+pub fn evacuate(heap: &mut Heap, eventlog: &mut Vec<GCEvent>, from: ObjAddr, to: ObjAddr) {
+    let mut original = heap.objects.get(&from).unwrap().clone();
+    let words = heap.words(from, original.size());
+    original.header.fwd_addr = Some(to);
+    move_object(heap, eventlog, from, to);
+    heap.store_words(to, &words);
+    if let Some(copy) = heap.objects.get_mut(&to) {
+        copy.header.fwd_addr = None;
+    }
+    heap.objects.insert(from, original);
+    heap.write_object_words(from);
+}
 
 pub fn move_object(heap: &mut Heap, eventlog: &mut Vec<GCEvent>, from: ObjAddr, to: ObjAddr) {
-    if heap.objects.get(&from).is_some() {
+    if heap.objects.contains_key(&from) {
         let size = heap.objects.get(&from).unwrap().size();
         eventlog.push(GCEvent::MoveObject { from, to, size });
         match heap.move_object(from, to) {
@@ -10,3 +32,131 @@ pub fn move_object(heap: &mut Heap, eventlog: &mut Vec<GCEvent>, from: ObjAddr,
         };
     }
 }
+
+/// Recomputes the free list from the gaps between objects, for compactors
+/// that slide objects over garbage without going through the free list.
+/// The large object space, if any, keeps track of its own pages.
+pub fn rebuild_free_list(heap: &mut Heap) {
+    let end = heap
+        .large_objects
+        .as_ref()
+        .map_or(heap.memory.len(), |los| los.start);
+    let mut free_list = FreeList::new(vec![]);
+    let mut cursor = 0;
+    for (addr, obj) in heap.objects.range(..end) {
+        if *addr > cursor {
+            free_list.insert(cursor, addr - cursor);
+        }
+        cursor = addr + obj.size();
+    }
+    if cursor < end {
+        free_list.insert(cursor, end - cursor);
+    }
+    heap.free_list = free_list;
+}
+
+/// Resets the weak references held by live objects whose targets are
+/// about to be reclaimed, i.e. that `is_live` rejects. So are the
+/// ephemerons whose keys are about to be reclaimed.
+pub fn clear_weak_refs(
+    heap: &mut Heap,
+    eventlog: &mut Vec<GCEvent>,
+    is_live: impl Fn(&Heap, ObjAddr) -> bool,
+) {
+    let mut dangling = vec![];
+    let mut dead_keys = vec![];
+    for (addr, obj) in &heap.objects {
+        if !is_live(heap, *addr) {
+            continue;
+        }
+        for (i, key, value) in ephemeron::pairs(obj) {
+            let cleared = *key == Address::Null && *value == Address::Null;
+            if !cleared && !ephemeron::points_to_live(heap, key, &is_live) {
+                dead_keys.push((*addr, i));
+            }
+        }
+        for (i, field) in obj.fields.iter().enumerate() {
+            if let Field::WeakRef {
+                addr: Address::Ptr(target),
+            } = field
+            {
+                let target_live = heap
+                    .lookup_object_addr(*target)
+                    .is_ok_and(|target_addr| is_live(heap, target_addr));
+                if !target_live {
+                    dangling.push((*addr, i, *target));
+                }
+            }
+        }
+    }
+
+    for (addr, i, target) in dangling {
+        let obj = heap.objects.get_mut(&addr).unwrap();
+        obj.fields[i] = Field::new_weak_ref(Address::Null);
+        eventlog.push(GCEvent::ClearWeakRef {
+            field: obj.field_addr(addr, i),
+            target,
+        });
+    }
+
+    for (addr, i) in dead_keys {
+        let obj = heap.objects.get_mut(&addr).unwrap();
+        obj.fields[i] = Field::new_ephemeron_key(Address::Null);
+        obj.fields[i + 1] = Field::new_ephemeron_value(Address::Null);
+        eventlog.push(GCEvent::ClearEphemeron {
+            field: obj.field_addr(addr, i),
+        });
+    }
+}
+
+/// Points the weak references and ephemerons held by live objects at the
+/// copies of their targets, for collectors that leave forwarding addresses
+/// behind. Dead targets are left to `clear_weak_refs`, which has to count
+/// forwarded objects as live.
+pub fn forward_weak_refs(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+    let mut forwarded = vec![];
+    for (addr, obj) in &heap.objects {
+        if obj.header.fwd_addr.is_some() {
+            continue;
+        }
+        for (i, field) in obj.fields.iter().enumerate() {
+            if let Field::WeakRef {
+                addr: Address::Ptr(old),
+            }
+            | Field::EphemeronKey {
+                addr: Address::Ptr(old),
+            }
+            | Field::EphemeronValue {
+                addr: Address::Ptr(old),
+            } = field
+            {
+                if let Some(new) = forwarding_address(heap, *old) {
+                    forwarded.push((*addr, i, *old, new));
+                }
+            }
+        }
+    }
+
+    for (addr, i, old, new) in forwarded {
+        if let Field::WeakRef {
+            addr: Address::Ptr(ptr),
+        }
+        | Field::EphemeronKey {
+            addr: Address::Ptr(ptr),
+        }
+        | Field::EphemeronValue {
+            addr: Address::Ptr(ptr),
+        } = &mut heap.objects.get_mut(&addr).unwrap().fields[i]
+        {
+            *ptr = new;
+        }
+        eventlog.push(GCEvent::UpdateFwdPtr { old, new });
+    }
+}
+
+/// Where `ptr` points to once the object it points into has been copied.
+fn forwarding_address(heap: &Heap, ptr: ObjAddr) -> Option<ObjAddr> {
+    let obj_addr = heap.lookup_object_addr(ptr).ok()?;
+    let fwd_addr = heap.objects.get(&obj_addr)?.header.fwd_addr?;
+    Some(fwd_addr + (ptr - obj_addr))
+}
@@ -0,0 +1,240 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    gc::{
+        common::{clear_weak_refs, move_object, rebuild_free_list},
+        ephemeron, finalization, large_object_space, GCEvent, SideTables,
+    },
+    heap::Heap,
+    object::{Address, Field, ObjAddr},
+};
+
+/// Number of heap words covered by one entry of the offset table.
+const BLOCK_SIZE: usize = 4;
+
+// Compressor,
+// introduced by Kermany and Petrank in 2006.
+// Liveness is recorded in a side bitmap, one bit per heap word, and object
+// headers are never touched. Counting the live words block by block yields
+// an offset table, from which the forwarding address of any live word is
+// its block's offset plus the marked bits preceding it within the block.
+// Since forwarding addresses can be computed on demand, pointers are
+// updated and objects moved in a single pass over the heap. A pinned
+// object keeps its address, so counting restarts at it: the live words
+// above it slide down to its end.
+pub fn compact(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) -> SideTables {
+    let mark_bitmap = mark(heap, eventlog);
+    clear_weak_refs(heap, eventlog, |_, addr| mark_bitmap[addr]);
+    large_object_space::sweep(heap, eventlog, |_, addr| mark_bitmap[addr]);
+    let pinned = pinned_objects(heap, &mark_bitmap);
+    let offset_table = compute_offsets(&mark_bitmap, &pinned, eventlog);
+    let tables = SideTables {
+        mark_bitmap,
+        block_size: BLOCK_SIZE,
+        offset_table,
+    };
+    relocate(heap, eventlog, &tables, &pinned);
+    rebuild_free_list(heap);
+    tables
+}
+
+fn mark(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) -> Vec<bool> {
+    eventlog.push(GCEvent::phase("mark bitmap".to_string()));
+    let mut bitmap = vec![false; heap.memory.len()];
+    let roots: Vec<ObjAddr> = heap
+        .roots
+        .iter()
+        .chain(heap.finalization_queue.iter())
+        .cloned()
+        .collect();
+    mark_from(heap, eventlog, &mut bitmap, roots);
+    mark_ephemerons(heap, eventlog, &mut bitmap);
+
+    let unreachable = finalization::unreachable_finalizable(heap, |_, addr| bitmap[addr]);
+    if !unreachable.is_empty() {
+        for addr in unreachable {
+            finalization::log_resurrection(heap, eventlog, addr);
+            mark_from(heap, eventlog, &mut bitmap, vec![addr]);
+            finalization::enqueue(heap, eventlog, addr);
+        }
+        mark_ephemerons(heap, eventlog, &mut bitmap);
+    }
+
+    bitmap
+}
+
+/// Marks the values of ephemerons whose keys are marked, iterating
+/// until no more values become reachable.
+fn mark_ephemerons(heap: &Heap, eventlog: &mut Vec<GCEvent>, bitmap: &mut [bool]) {
+    if !ephemeron::any(heap) {
+        return;
+    }
+    for iteration in 1.. {
+        let ready = ephemeron::ready_values(heap, |_, addr| bitmap[addr]);
+        eventlog.push(GCEvent::EphemeronIteration {
+            iteration,
+            ready: ready.len(),
+        });
+        if ready.is_empty() {
+            break;
+        }
+        mark_from(heap, eventlog, bitmap, ready);
+    }
+}
+
+fn mark_from(heap: &Heap, eventlog: &mut Vec<GCEvent>, bitmap: &mut [bool], roots: Vec<ObjAddr>) {
+    let mut stack: Vec<ObjAddr> = roots.into_iter().rev().collect();
+
+    while let Some(ptr) = stack.pop() {
+        let Ok(addr) = heap.lookup_object_addr(ptr) else {
+            continue;
+        };
+        if bitmap[addr] {
+            continue;
+        }
+        let obj = heap.objects.get(&addr).unwrap();
+
+        // Padding up to the next aligned address is marked as well,
+        // so that packing the marked words keeps objects aligned
+        let end = heap.aligned_position(addr + obj.size()).min(bitmap.len());
+        bitmap[addr..end].fill(true);
+        eventlog.push(GCEvent::MarkObject {
+            addr,
+            size: obj.size(),
+            worker: None,
+        });
+
+        for field in obj.fields.iter().rev() {
+            if let Field::Ref {
+                addr: Address::Ptr(ptr),
+            } = field
+            {
+                stack.push(*ptr);
+            }
+        }
+    }
+}
+
+/// Live pinned objects outside of the large object space,
+/// which keeps its objects in place anyway.
+fn pinned_objects(heap: &Heap, bitmap: &[bool]) -> BTreeSet<ObjAddr> {
+    let large_start = heap
+        .large_objects
+        .as_ref()
+        .map_or(usize::MAX, |los| los.start);
+    heap.objects
+        .iter()
+        .filter(|(addr, obj)| obj.header.pinned && bitmap[**addr] && **addr < large_start)
+        .map(|(addr, _)| *addr)
+        .collect()
+}
+
+/// The offset of a block is where its first live word moves to: the number
+/// of live words in all blocks before it, or past the last pinned object
+/// before it, the words from that object on.
+fn compute_offsets(
+    bitmap: &[bool],
+    pinned: &BTreeSet<ObjAddr>,
+    eventlog: &mut Vec<GCEvent>,
+) -> Vec<usize> {
+    eventlog.push(GCEvent::phase("compute offsets".to_string()));
+    let mut live = 0;
+    bitmap
+        .chunks(BLOCK_SIZE)
+        .enumerate()
+        .map(|(block, bits)| {
+            let offset = live;
+            for (addr, bit) in (block * BLOCK_SIZE..).zip(bits) {
+                if pinned.contains(&addr) {
+                    live = addr;
+                }
+                if *bit {
+                    live += 1;
+                }
+            }
+            offset
+        })
+        .collect()
+}
+
+/// The block's offset plus the live words preceding `addr` within the
+/// block, counted from the last pinned object up to `addr` if there is one.
+fn forwarding_address(tables: &SideTables, pinned: &BTreeSet<ObjAddr>, addr: ObjAddr) -> ObjAddr {
+    let block_start = addr - addr % tables.block_size;
+    let (from, offset) = match pinned.range(block_start..=addr).next_back() {
+        Some(pin) => (*pin, *pin),
+        None => (block_start, tables.offset_table[addr / tables.block_size]),
+    };
+    let preceding = tables.mark_bitmap[from..addr]
+        .iter()
+        .filter(|bit| **bit)
+        .count();
+    offset + preceding
+}
+
+fn relocate(
+    heap: &mut Heap,
+    eventlog: &mut Vec<GCEvent>,
+    tables: &SideTables,
+    pinned: &BTreeSet<ObjAddr>,
+) {
+    eventlog.push(GCEvent::phase("compact".to_string()));
+    let addrs: Vec<ObjAddr> = heap.objects.keys().cloned().collect();
+    // Large objects are never moved, so they keep their addresses
+    let large_start = heap
+        .large_objects
+        .as_ref()
+        .map_or(usize::MAX, |los| los.start);
+    let forward = |addr: ObjAddr| {
+        if addr >= large_start {
+            addr
+        } else {
+            forwarding_address(tables, pinned, addr)
+        }
+    };
+
+    for addr in addrs {
+        if !tables.mark_bitmap.get(addr).copied().unwrap_or(false) {
+            eventlog.push(finalization::reclaim_event(heap, addr));
+            heap.objects.remove(&addr);
+            continue;
+        }
+
+        let obj = heap.objects.get_mut(&addr).unwrap();
+        for field in &mut obj.fields {
+            if let Field::Ref {
+                addr: Address::Ptr(old),
+            }
+            | Field::WeakRef {
+                addr: Address::Ptr(old),
+            }
+            | Field::EphemeronKey {
+                addr: Address::Ptr(old),
+            }
+            | Field::EphemeronValue {
+                addr: Address::Ptr(old),
+            } = field
+            {
+                if !tables.mark_bitmap.get(*old).copied().unwrap_or(false) {
+                    continue;
+                }
+                let new = forward(*old);
+                if new != *old {
+                    eventlog.push(GCEvent::UpdateFwdPtr { old: *old, new });
+                    *old = new;
+                }
+            }
+        }
+
+        if pinned.contains(&addr) {
+            eventlog.push(GCEvent::SkipPinned {
+                addr,
+                size: heap.objects[&addr].size(),
+            });
+        }
+        let new_addr = forward(addr);
+        if new_addr != addr {
+            move_object(heap, eventlog, addr, new_addr);
+        }
+    }
+}
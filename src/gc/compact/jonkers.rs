@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use crate::{
+    gc::{
+        common::{clear_weak_refs, move_object, rebuild_free_list},
+        compact::{is_large_object, is_pinned, log_pinned},
+        finalization::reclaim_event,
+        large_object_space,
+        object_marker::{is_marked, unmark},
+        GCEvent,
+    },
+    heap::Heap,
+    object::{Address, Field, ObjAddr},
+};
+
+/// What a threaded field held: the offset of the pointer into its referent,
+/// and the kind of field to restore.
+struct Threaded {
+    offset: usize,
+    kind: fn(Address) -> Field,
+}
+
+// Threaded compaction,
+// introduced by Jonkers in 1979.
+// No forwarding address is stored. Instead, every field pointing to an
+// object is threaded onto a chain starting at the object's header: the
+// header holds the last threaded field, which holds the previous header
+// value, and so on. Once the destination of the object is known, unthreading
+// walks the chain and points every field at it. The first sliding pass
+// resolves pointers to objects higher up in the heap, the second one
+// resolves the remaining pointers and moves the objects.
+pub fn compact(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+    clear_weak_refs(heap, eventlog, is_marked);
+    large_object_space::sweep(heap, eventlog, is_marked);
+    // A chain links field addresses only, so the offsets of
+    // interior pointers and the kind of each field are kept on the side.
+    let mut offsets = HashMap::new();
+    update_forward_references(heap, eventlog, &mut offsets);
+    update_backward_references(heap, eventlog, &offsets);
+    rebuild_free_list(heap);
+}
+
+fn update_forward_references(
+    heap: &mut Heap,
+    eventlog: &mut Vec<GCEvent>,
+    offsets: &mut HashMap<ObjAddr, Threaded>,
+) {
+    eventlog.push(GCEvent::phase("update_forward_references".to_string()));
+    let live: Vec<ObjAddr> = heap
+        .objects
+        .keys()
+        .filter(|addr| is_marked(heap, **addr))
+        .cloned()
+        .collect();
+    let mut free = 0;
+
+    for addr in live {
+        let dest = destination(heap, addr, &mut free);
+        unthread(heap, eventlog, offsets, addr, dest);
+        let size = heap.objects.get(&addr).unwrap().size();
+        for field in addr..addr + size {
+            thread(heap, eventlog, offsets, field);
+        }
+    }
+}
+
+fn update_backward_references(
+    heap: &mut Heap,
+    eventlog: &mut Vec<GCEvent>,
+    offsets: &HashMap<ObjAddr, Threaded>,
+) {
+    eventlog.push(GCEvent::phase("update_backward_references".to_string()));
+    let addrs: Vec<ObjAddr> = heap.objects.keys().cloned().collect();
+    let mut free = 0;
+
+    for addr in addrs {
+        if !is_marked(heap, addr) {
+            eventlog.push(reclaim_event(heap, addr));
+            heap.objects.remove(&addr);
+            continue;
+        }
+
+        let dest = destination(heap, addr, &mut free);
+        unthread(heap, eventlog, offsets, addr, dest);
+        log_pinned(heap, eventlog, addr);
+        if addr != dest {
+            move_object(heap, eventlog, addr, dest);
+        }
+        unmark(heap, dest);
+    }
+}
+
+/// Where the live object at `addr` slides to, advancing `free` past it.
+/// Large objects stay in place, they come after all the others. Pinned
+/// objects stay in place too, and the objects above them slide down to
+/// their end.
+fn destination(heap: &Heap, addr: ObjAddr, free: &mut ObjAddr) -> ObjAddr {
+    if is_large_object(heap, addr) {
+        return addr;
+    }
+    let dest = if is_pinned(heap, addr) { addr } else { *free };
+    *free = heap.aligned_position(dest + heap.objects.get(&addr).unwrap().size());
+    dest
+}
+
+/// Threads the pointer stored at `field` onto the header of its referent.
+fn thread(
+    heap: &mut Heap,
+    eventlog: &mut Vec<GCEvent>,
+    offsets: &mut HashMap<ObjAddr, Threaded>,
+    field: ObjAddr,
+) {
+    let (ptr, kind): (ObjAddr, fn(Address) -> Field) = match heap.field_at(field) {
+        Some(Field::Ref {
+            addr: Address::Ptr(ptr),
+        }) => (*ptr, Field::new_ref),
+        Some(Field::WeakRef {
+            addr: Address::Ptr(ptr),
+        }) => (*ptr, Field::new_weak_ref),
+        Some(Field::EphemeronKey {
+            addr: Address::Ptr(ptr),
+        }) => (*ptr, Field::new_ephemeron_key),
+        Some(Field::EphemeronValue {
+            addr: Address::Ptr(ptr),
+        }) => (*ptr, Field::new_ephemeron_value),
+        _ => return,
+    };
+    let Ok(obj_addr) = heap.lookup_object_addr(ptr) else {
+        return;
+    };
+    offsets.insert(
+        field,
+        Threaded {
+            offset: ptr - obj_addr,
+            kind,
+        },
+    );
+
+    let header = &mut heap.objects.get_mut(&obj_addr).unwrap().header;
+    let prev = header.thread.replace(field);
+    *field_at_mut(heap, field) = Field::new_ref(prev.map_or(Address::Null, Address::Ptr));
+    eventlog.push(GCEvent::ThreadPointer {
+        field,
+        obj: obj_addr,
+    });
+}
+
+/// Points every field threaded onto the object at `addr` to `new_addr`
+/// and restores the header.
+fn unthread(
+    heap: &mut Heap,
+    eventlog: &mut Vec<GCEvent>,
+    offsets: &HashMap<ObjAddr, Threaded>,
+    addr: ObjAddr,
+    new_addr: ObjAddr,
+) {
+    let mut link = heap.objects.get_mut(&addr).unwrap().header.thread.take();
+
+    while let Some(field) = link {
+        let slot = field_at_mut(heap, field);
+        link = match slot {
+            Field::Ref {
+                addr: Address::Ptr(next),
+            } => Some(*next),
+            _ => None,
+        };
+        let threaded = &offsets[&field];
+        let new = new_addr + threaded.offset;
+        *slot = (threaded.kind)(Address::Ptr(new));
+        eventlog.push(GCEvent::UnthreadPointer { field, new });
+    }
+}
+
+fn field_at_mut(heap: &mut Heap, address: ObjAddr) -> &mut Field {
+    let obj_addr = heap.lookup_object_addr(address).unwrap();
+    let obj = heap.objects.get_mut(&obj_addr).unwrap();
+    obj.field_mut(address - obj_addr).unwrap()
+}
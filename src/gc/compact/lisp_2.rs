@@ -2,7 +2,9 @@ use std::collections::HashMap;
 
 use crate::{
     gc::{
-        common::move_object,
+        common::{clear_weak_refs, move_object, rebuild_free_list},
+        compact::{reclaim_garbage, stays_in_place},
+        large_object_space,
         object_marker::{is_marked, unmark},
         GCEvent,
     },
@@ -13,9 +15,14 @@ use crate::{
 pub fn compact(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
     let start: usize = 0;
     let end: usize = heap.last_object_addr().unwrap();
+    clear_weak_refs(heap, eventlog, is_marked);
+    large_object_space::sweep(heap, eventlog, is_marked);
     compute_locations(heap, eventlog, start, end, start);
     update_references(heap, eventlog);
+    // Live objects slide over the garbage, which is dropped beforehand
+    reclaim_garbage(heap, eventlog);
     relocate(heap, eventlog, start, end);
+    rebuild_free_list(heap);
 }
 
 fn compute_locations(
@@ -33,10 +40,16 @@ fn compute_locations(
         let mut size = 0;
 
         if is_marked(heap, scan) {
+            // A pinned or large object keeps its address
+            // and the objects above it slide down to its end
+            if stays_in_place(heap, eventlog, scan) {
+                free = scan;
+            }
             if let Some(obj) = heap.objects.get_mut(&scan) {
                 obj.header.fwd_addr = Some(free);
                 size = obj.size();
             }
+            heap.write_object_words(scan);
         }
 
         if size > 0 {
@@ -58,10 +71,19 @@ fn update_references(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
 
     // First pass: collect all addresses that need to be updated
     for (addr, obj) in &heap.objects {
-        if obj.header.marked {
+        if is_marked(heap, *addr) {
             for (i, field) in obj.fields.iter().enumerate() {
                 if let Field::Ref {
                     addr: Address::Ptr(a),
+                }
+                | Field::WeakRef {
+                    addr: Address::Ptr(a),
+                }
+                | Field::EphemeronKey {
+                    addr: Address::Ptr(a),
+                }
+                | Field::EphemeronValue {
+                    addr: Address::Ptr(a),
                 } = field
                 {
                     let fwd_obj_addr = heap.lookup_object_addr(*a).unwrap();
@@ -88,6 +110,15 @@ fn update_references(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
             for (i, fwd_addr) in updates {
                 if let Field::Ref {
                     addr: Address::Ptr(ref mut a_ref),
+                }
+                | Field::WeakRef {
+                    addr: Address::Ptr(ref mut a_ref),
+                }
+                | Field::EphemeronKey {
+                    addr: Address::Ptr(ref mut a_ref),
+                }
+                | Field::EphemeronValue {
+                    addr: Address::Ptr(ref mut a_ref),
                 } = &mut obj.fields[i]
                 {
                     if *a_ref == fwd_addr {
@@ -106,11 +137,16 @@ fn update_references(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
 
 fn relocate(heap: &mut Heap, eventlog: &mut Vec<GCEvent>, start: usize, end: usize) {
     eventlog.push(GCEvent::phase("relocate  ".to_string()));
-    let mut scan = start;
+    // With the garbage gone, `start` need not hold an object anymore
+    let Some(mut scan) = heap.objects.range(start..).next().map(|(addr, _)| *addr) else {
+        return;
+    };
 
     while scan <= end {
+        // Looked up before moving, since the move vacates `scan`
+        let next = heap.next_object_addr(scan);
         if let Some(obj) = heap.objects.get(&scan) {
-            if obj.header.marked {
+            if is_marked(heap, scan) {
                 if let Some(dest) = obj.header.fwd_addr {
                     if scan != dest {
                         move_object(heap, eventlog, scan, dest);
@@ -121,7 +157,7 @@ fn relocate(heap: &mut Heap, eventlog: &mut Vec<GCEvent>, start: usize, end: usi
         }
 
         // Move to the next object
-        if let Some(addr) = heap.next_object_addr(scan) {
+        if let Some(addr) = next {
             scan = addr;
         } else {
             break;
@@ -1,2 +1,63 @@
+pub mod compressor;
+pub mod jonkers;
 pub mod lisp_2;
 pub mod two_finger;
+
+use crate::{
+    gc::{finalization::reclaim_event, object_marker::is_marked, GCEvent},
+    heap::Heap,
+    object::ObjAddr,
+};
+
+/// Whether the object at `addr` must stay in place, i.e. it is pinned or
+/// it lives in the large object space. Skipped moves of pinned objects are
+/// logged so that the client can tell why the object did not slide.
+fn stays_in_place(heap: &Heap, eventlog: &mut Vec<GCEvent>, addr: ObjAddr) -> bool {
+    if is_large_object(heap, addr) {
+        return true;
+    }
+    log_pinned(heap, eventlog, addr)
+}
+
+/// Logs that the object at `addr` is not moved if it is pinned.
+/// Returns whether it is.
+fn log_pinned(heap: &Heap, eventlog: &mut Vec<GCEvent>, addr: ObjAddr) -> bool {
+    match heap.objects.get(&addr) {
+        Some(obj) if obj.header.pinned => {
+            eventlog.push(GCEvent::SkipPinned {
+                addr,
+                size: obj.size(),
+            });
+            true
+        }
+        _ => false,
+    }
+}
+
+fn is_pinned(heap: &Heap, addr: ObjAddr) -> bool {
+    heap.objects.get(&addr).is_some_and(|obj| obj.header.pinned)
+}
+
+fn is_large_object(heap: &Heap, addr: ObjAddr) -> bool {
+    heap.large_objects
+        .as_ref()
+        .is_some_and(|los| los.contains(addr))
+}
+
+/// Frees the objects the mark did not reach, for compactors that would
+/// otherwise move live objects over them without dropping them.
+fn reclaim_garbage(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+    let garbage: Vec<ObjAddr> = heap
+        .objects
+        .keys()
+        .filter(|addr| !is_marked(heap, **addr))
+        .cloned()
+        .collect();
+    for addr in garbage {
+        let event = reclaim_event(heap, addr);
+        match heap.free_object(addr) {
+            Ok(_) => eventlog.push(event),
+            Err(_e) => panic!("reclaim_garbage:free_object at {addr:}"),
+        }
+    }
+}
@@ -2,7 +2,9 @@ use std::collections::HashMap;
 
 use crate::{
     gc::{
-        common::move_object,
+        common::{clear_weak_refs, move_object, rebuild_free_list},
+        compact::{reclaim_garbage, stays_in_place},
+        large_object_space,
         object_marker::{is_marked, unmark},
         GCEvent,
     },
@@ -13,32 +15,37 @@ use crate::{
 // Two-Finger Algorithm,
 // introduced by Edwards in 1974
 pub fn compact(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
-    let start: usize = 0;
-    let end: usize = heap.last_object_addr().unwrap();
-    let forwarding_ptrs = relocate(heap, eventlog, start, end);
+    clear_weak_refs(heap, eventlog, is_marked);
+    large_object_space::sweep(heap, eventlog, is_marked);
+    // Gaps are filled from the top of the heap, whether they were left by
+    // this collection or by an earlier one
+    reclaim_garbage(heap, eventlog);
+    let forwarding_ptrs = relocate(heap, eventlog);
     update_references(heap, &forwarding_ptrs, eventlog);
+    rebuild_free_list(heap);
 }
 
-fn relocate(
-    heap: &mut Heap,
-    eventlog: &mut Vec<GCEvent>,
-    start: ObjAddr,
-    end: ObjAddr,
-) -> HashMap<usize, usize> {
+fn relocate(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) -> HashMap<usize, usize> {
     eventlog.push(GCEvent::phase("relocate".to_string()));
-    let mut free = start;
-    let mut scan = end;
+    let end = heap
+        .large_objects
+        .as_ref()
+        .map_or(heap.memory.len(), |los| los.start);
+    // Objects above the free finger, the scan finger takes them from the top
+    let mut scan_objects: Vec<ObjAddr> = heap.objects.keys().cloned().collect();
+    let mut free = 0;
 
     let mut forwarding_pointers = HashMap::new();
 
-    while free < scan {
-        while is_marked(heap, free) {
-            unmark(heap, free);
-            free = heap.next_object_addr(free).unwrap();
+    while let Some(scan) = scan_objects.pop() {
+        free = next_gap(heap, free);
+        if scan <= free {
+            break;
         }
-
-        while !is_marked(heap, scan) && scan > free {
-            scan = heap.prev_object_addr(scan).unwrap();
+        // Pinned and large objects stay where they are, the gap
+        // at free is left for the next live object below them
+        if stays_in_place(heap, eventlog, scan) {
+            continue;
         }
 
         // Note, that this only works well for regions with allocated objects of same size.
@@ -46,26 +53,43 @@ fn relocate(
         // gap at free closely matching the size of the live object at scan. Unless this
         // algorithm is used on fixed-size objects, the degree of defragmentation might
         // be very poor indeed."
-        if scan > free && can_fit_into(heap, scan, free) {
-            unmark(heap, scan);
+        let gap_end = heap
+            .objects
+            .range(free..)
+            .next()
+            .map_or(end, |(addr, _)| *addr);
+        if free + heap.objects.get(&scan).unwrap().size() <= gap_end {
             save_forward_ptrs(heap, &mut forwarding_pointers, scan, free);
             move_object(heap, eventlog, scan, free);
-            if let Some(next_free) = heap.next_object_addr(free) {
-                free = next_free;
-            } else {
-                break;
-            }
-            if let Some(prev_scan) = heap.prev_object_addr(scan) {
-                scan = prev_scan;
-            } else {
-                break;
-            }
+        } else {
+            // The gap is too small, the object tries the next one
+            free = gap_end;
+            scan_objects.push(scan);
         }
     }
 
+    let live: Vec<ObjAddr> = heap.objects.keys().cloned().collect();
+    for addr in live {
+        unmark(heap, addr);
+    }
     forwarding_pointers
 }
 
+/// The first aligned address at or above `from` that no object occupies.
+fn next_gap(heap: &Heap, from: ObjAddr) -> ObjAddr {
+    let mut free = heap.aligned_position(from);
+    for (addr, obj) in &heap.objects {
+        if addr + obj.size() <= free {
+            continue;
+        }
+        if free < *addr {
+            break;
+        }
+        free = heap.aligned_position(addr + obj.size());
+    }
+    free
+}
+
 fn save_forward_ptrs(
     heap: &Heap,
     forwarding_pointers: &mut HashMap<usize, usize>,
@@ -74,12 +98,21 @@ fn save_forward_ptrs(
 ) {
     let obj = heap.objects.get(&from).unwrap();
 
+    // The header moves along with the fields
+    for offset_from_start in 0..obj.header.words {
+        forwarding_pointers.insert(from + offset_from_start, to + offset_from_start);
+    }
+
     // Iterate over the fields of the object
-    for (offset_from_start, field) in obj.fields.iter().enumerate() {
+    for (i, field) in obj.fields.iter().enumerate() {
+        let offset_from_start = obj.header.words + i;
         match field {
             Field::Ref {
                 addr: Address::Ptr(_),
-            } => {
+            }
+            | Field::WeakRef { .. }
+            | Field::EphemeronKey { .. }
+            | Field::EphemeronValue { .. } => {
                 let old_ptr = from + offset_from_start;
                 let new_ptr = to + offset_from_start;
                 forwarding_pointers.insert(old_ptr, new_ptr);
@@ -94,12 +127,6 @@ fn save_forward_ptrs(
     }
 }
 
-fn can_fit_into(heap: &Heap, move_in: ObjAddr, dest: ObjAddr) -> bool {
-    let obj_to_be_moved = heap.objects.get(&move_in).unwrap();
-    let obj_to_be_removed = heap.objects.get(&dest).unwrap();
-    obj_to_be_moved.size() <= obj_to_be_removed.size()
-}
-
 fn update_references(
     heap: &mut Heap,
     forwarding_pointers: &HashMap<usize, usize>,
@@ -109,6 +136,15 @@ fn update_references(
         for field in &mut object.fields {
             if let Field::Ref {
                 addr: Address::Ptr(old),
+            }
+            | Field::WeakRef {
+                addr: Address::Ptr(old),
+            }
+            | Field::EphemeronKey {
+                addr: Address::Ptr(old),
+            }
+            | Field::EphemeronValue {
+                addr: Address::Ptr(old),
             } = field
             {
                 if let Some(new_addr) = forwarding_pointers.get(old) {
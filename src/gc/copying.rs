@@ -0,0 +1,250 @@
+use crate::{
+    error::VMError,
+    free_list::FreeList,
+    heap::Heap,
+    object::{Address, Field, ObjAddr},
+};
+
+use super::{
+    common::{clear_weak_refs, evacuate, forward_weak_refs},
+    ephemeron,
+    stats::GCStats,
+    GCEvent, GCType, GarbageCollector,
+};
+
+// Semispace copying collector,
+// introduced by Cheney in 1970.
+// The heap is split into two halves of equal size. The mutator allocates
+// in from-space only; a collection evacuates every reachable object into
+// to-space and then flips the roles of the two halves. To-space doubles as
+// the breadth-first work queue: everything between `scan` and `free` has
+// been copied but its fields have not been forwarded yet. Weak references
+// are not traced; once to-space is scanned, they follow their targets to
+// to-space or are cleared if the targets were left behind. Ephemeron values
+// are evacuated once their keys are, which may take several rounds.
+// In the word memory model objects are copied word by word, forwarding
+// pointers are read from header words and pointer fields are read from
+// and written to their cells; the object table follows the cells.
+pub struct Copying {
+    from_space: usize,
+    to_space: usize,
+    extent: usize,
+}
+
+impl Default for Copying {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Copying {
+    pub fn new() -> Self {
+        Copying {
+            from_space: 0,
+            to_space: 0,
+            extent: 0,
+        }
+    }
+
+    fn in_from_space(&self, addr: ObjAddr) -> bool {
+        self.from_space <= addr && addr < self.from_space + self.extent
+    }
+
+    /// Whether the object at `addr` is in to-space or has been evacuated.
+    fn survives(&self, heap: &Heap, addr: ObjAddr) -> bool {
+        !self.in_from_space(addr) || heap.forwarded_to(addr).is_some()
+    }
+
+    /// Returns the to-space address that `ptr` has to be rewritten to.
+    /// The object containing `ptr` is evacuated first unless its header
+    /// already holds a forwarding address. Fails if the object does not
+    /// fit in what is left of to-space, which alignment padding can cause.
+    fn forward(
+        &self,
+        heap: &mut Heap,
+        eventlog: &mut Vec<GCEvent>,
+        free: &mut usize,
+        ptr: ObjAddr,
+    ) -> Result<Option<ObjAddr>, VMError> {
+        if !self.in_from_space(ptr) {
+            return Ok(None);
+        }
+        let Ok(obj_addr) = heap.lookup_object_addr(ptr) else {
+            return Ok(None);
+        };
+        let offset = ptr - obj_addr;
+        if let Some(fwd_addr) = heap.forwarded_to(obj_addr) {
+            return Ok(Some(fwd_addr + offset));
+        }
+        let Some(obj) = heap.objects.get(&obj_addr) else {
+            return Ok(None);
+        };
+
+        let to = heap.aligned_position(*free);
+        if to + obj.size() > self.to_space + self.extent {
+            return Err(VMError::OutOfMemory);
+        }
+        *free = to + obj.size();
+        evacuate(heap, eventlog, obj_addr, to);
+        Ok(Some(to + offset))
+    }
+
+    fn evacuate_roots(
+        &self,
+        heap: &mut Heap,
+        eventlog: &mut Vec<GCEvent>,
+        free: &mut usize,
+    ) -> Result<(), VMError> {
+        eventlog.push(GCEvent::phase("evacuate roots".to_string()));
+        let roots: Vec<ObjAddr> = heap.roots.iter().cloned().collect();
+        for root in roots {
+            if let Some(new_root) = self.forward(heap, eventlog, free, root)? {
+                heap.roots.remove(&root);
+                heap.roots.insert(new_root);
+            }
+        }
+        Ok(())
+    }
+
+    fn scan_to_space(
+        &self,
+        heap: &mut Heap,
+        eventlog: &mut Vec<GCEvent>,
+        scan: &mut usize,
+        free: &mut usize,
+    ) -> Result<(), VMError> {
+        eventlog.push(GCEvent::phase("scan to-space".to_string()));
+
+        while let Some((obj_addr, obj)) = heap.objects.range(*scan..*free).next() {
+            let (obj_addr, size, fields) = (*obj_addr, obj.size(), obj.fields.len());
+            for i in 0..fields {
+                let Some(Field::Ref {
+                    addr: Address::Ptr(old),
+                }) = heap.load_field(obj_addr, i)
+                else {
+                    continue;
+                };
+                if let Some(new) = self.forward(heap, eventlog, free, old)? {
+                    heap.store_field(obj_addr, i, Field::new_ref(Address::Ptr(new)))?;
+                    eventlog.push(GCEvent::UpdateFwdPtr { old, new });
+                }
+            }
+
+            *scan = obj_addr + size;
+        }
+        Ok(())
+    }
+
+    /// Evacuates the values of ephemerons whose keys have been evacuated,
+    /// along with everything they point to, until no more values do.
+    fn evacuate_ephemerons(
+        &self,
+        heap: &mut Heap,
+        eventlog: &mut Vec<GCEvent>,
+        scan: &mut usize,
+        free: &mut usize,
+    ) -> Result<(), VMError> {
+        if !ephemeron::any(heap) {
+            return Ok(());
+        }
+        for iteration in 1.. {
+            let ready = ephemeron::ready_values(heap, |heap, addr| self.survives(heap, addr));
+            eventlog.push(GCEvent::EphemeronIteration {
+                iteration,
+                ready: ready.len(),
+            });
+            if ready.is_empty() {
+                break;
+            }
+            for addr in ready {
+                self.forward(heap, eventlog, free, addr)?;
+            }
+            self.scan_to_space(heap, eventlog, scan, free)?;
+        }
+        Ok(())
+    }
+
+    /// Forwards the weak references and ephemerons of to-space objects whose
+    /// targets were evacuated, and clears the ones whose targets were not.
+    fn update_weak_refs(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        eventlog.push(GCEvent::phase("update weak references".to_string()));
+        clear_weak_refs(heap, eventlog, |heap, addr| self.survives(heap, addr));
+        forward_weak_refs(heap, eventlog);
+    }
+
+    /// Drops everything left in from-space: forwarding stubs of evacuated
+    /// objects and unreachable objects alike.
+    fn release_from_space(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        eventlog.push(GCEvent::phase("release from-space".to_string()));
+        let from_space_objects: Vec<ObjAddr> = heap
+            .objects
+            .range(self.from_space..self.from_space + self.extent)
+            .map(|(addr, _)| *addr)
+            .collect();
+
+        for addr in from_space_objects {
+            let obj = heap.objects.remove(&addr).unwrap();
+            if obj.header.fwd_addr.is_none() {
+                heap.roots.remove(&addr);
+                eventlog.push(GCEvent::FreeObject {
+                    addr,
+                    size: obj.size(),
+                });
+            }
+        }
+    }
+}
+
+impl GarbageCollector for Copying {
+    fn collect(&mut self, heap: &mut Heap) -> Result<(GCStats, Vec<GCEvent>), VMError> {
+        let mut eventlog = vec![GCEvent::phase("Copying: START".to_string())];
+        let mut scan = self.to_space;
+        let mut free = self.to_space;
+
+        self.evacuate_roots(heap, &mut eventlog, &mut free)?;
+        self.scan_to_space(heap, &mut eventlog, &mut scan, &mut free)?;
+        self.evacuate_ephemerons(heap, &mut eventlog, &mut scan, &mut free)?;
+        self.update_weak_refs(heap, &mut eventlog);
+        self.release_from_space(heap, &mut eventlog);
+
+        // Everything past `free` in to-space is available for allocation.
+        let to_space_end = self.to_space + self.extent;
+        heap.free_list = FreeList::new(vec![]);
+        if free < to_space_end {
+            heap.free_list.insert(free, to_space_end - free);
+        }
+
+        eventlog.push(GCEvent::phase("flip".to_string()));
+        std::mem::swap(&mut self.from_space, &mut self.to_space);
+        eventlog.push(GCEvent::phase("Copying: END".to_string()));
+
+        Ok((GCStats::new(), eventlog))
+    }
+
+    /// Splits the heap in halves. Configurations are validated to have a
+    /// heap that splits evenly, so no word is left out of both semispaces.
+    fn init_heap(&mut self, heap: &mut Heap) {
+        self.extent = heap.memory.len() / 2;
+        self.from_space = 0;
+        self.to_space = self.extent;
+
+        // Only from-space is handed to the allocator.
+        heap.free_list = FreeList::new(vec![]);
+        if self.extent > 0 {
+            heap.free_list.insert(self.from_space, self.extent);
+        }
+    }
+
+    /// The semispaces are laid out once for the initial heap size.
+    fn resizable_heap(&self) -> bool {
+        false
+    }
+
+    fn ty(&self) -> GCType {
+        GCType::Copying
+    }
+
+    fn new_instance(&self) -> Box<dyn GarbageCollector> {
+        Box::new(Copying::new())
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{
+    heap::Heap,
+    object::{Address, Field, ObjAddr, Object},
+};
+
+// Ephemerons,
+// introduced by Hayes in 1997.
+// An ephemeron is a key/value pair laid out as an `EphemeronKey` field
+// followed by an `EphemeronValue` field. Neither is traced along with the
+// object holding them: the value is traced only once the key turns out to
+// be reachable through some other path. Since tracing a value may make
+// further keys reachable, marking iterates over the ephemerons of live
+// objects until an iteration traces nothing new. Pairs whose keys die
+// are cleared.
+
+/// Ephemeron pairs of `obj`, as the index of the key field with the key
+/// and the value.
+pub fn pairs(obj: &Object) -> impl Iterator<Item = (usize, &Address, &Address)> {
+    obj.fields
+        .windows(2)
+        .enumerate()
+        .filter_map(|(i, window)| match window {
+            [Field::EphemeronKey { addr: key }, Field::EphemeronValue { addr: value }] => {
+                Some((i, key, value))
+            }
+            _ => None,
+        })
+}
+
+pub fn any(heap: &Heap) -> bool {
+    heap.objects.values().any(|obj| pairs(obj).next().is_some())
+}
+
+/// Whether `addr` points into an object accepted by `is_live`.
+pub fn points_to_live(
+    heap: &Heap,
+    addr: &Address,
+    is_live: impl Fn(&Heap, ObjAddr) -> bool,
+) -> bool {
+    match addr {
+        Address::Ptr(ptr) => heap
+            .lookup_object_addr(*ptr)
+            .is_ok_and(|obj_addr| is_live(heap, obj_addr)),
+        Address::Null => false,
+    }
+}
+
+/// Objects that the ephemerons of live objects make reachable, i.e. the
+/// values not accepted by `is_live` whose keys are.
+pub fn ready_values(heap: &Heap, is_live: impl Fn(&Heap, ObjAddr) -> bool) -> Vec<ObjAddr> {
+    let mut ready = vec![];
+    for (addr, obj) in &heap.objects {
+        if !is_live(heap, *addr) {
+            continue;
+        }
+        for (_, key, value) in pairs(obj) {
+            if !points_to_live(heap, key, &is_live) {
+                continue;
+            }
+            if let Address::Ptr(ptr) = value {
+                if let Ok(value_addr) = heap.lookup_object_addr(*ptr) {
+                    if !is_live(heap, value_addr) && !ready.contains(&value_addr) {
+                        ready.push(value_addr);
+                    }
+                }
+            }
+        }
+    }
+    ready
+}
@@ -0,0 +1,67 @@
+use crate::{
+    heap::Heap,
+    object::{Finalization, ObjAddr},
+};
+
+use super::GCEvent;
+
+// Finalization,
+// as found in Java and .NET.
+// A finalizable object the mark finds unreachable is not reclaimed: it is
+// resurrected together with everything it points to, and queued so that the
+// mutator can run its finalizer with `RunFinalizers`. Queued objects are
+// roots until then, and the object is only reclaimed by the first
+// collection that finds it unreachable after its finalizer ran.
+
+/// Pending finalizable objects rejected by `is_live` once marking is done.
+pub fn unreachable_finalizable(
+    heap: &Heap,
+    is_live: impl Fn(&Heap, ObjAddr) -> bool,
+) -> Vec<ObjAddr> {
+    heap.objects
+        .iter()
+        .filter(|(addr, obj)| {
+            obj.header.finalization == Finalization::Pending && !is_live(heap, **addr)
+        })
+        .map(|(addr, _)| *addr)
+        .collect()
+}
+
+pub fn log_resurrection(heap: &Heap, eventlog: &mut Vec<GCEvent>, addr: ObjAddr) {
+    eventlog.push(GCEvent::ResurrectObject {
+        addr,
+        size: heap.objects.get(&addr).unwrap().size(),
+    });
+}
+
+/// Puts a resurrected object on the finalization queue.
+pub fn enqueue(heap: &mut Heap, eventlog: &mut Vec<GCEvent>, addr: ObjAddr) {
+    if let Some(obj) = heap.objects.get_mut(&addr) {
+        obj.header.finalization = Finalization::Queued;
+        heap.finalization_queue.push_back(addr);
+        eventlog.push(GCEvent::EnqueueFinalizer { addr });
+    }
+}
+
+/// Drains the finalization queue, running every finalizer in order.
+pub fn run_finalizers(heap: &mut Heap) -> Vec<GCEvent> {
+    let mut eventlog = vec![];
+    while let Some(addr) = heap.finalization_queue.pop_front() {
+        if let Some(obj) = heap.objects.get_mut(&addr) {
+            obj.header.finalization = Finalization::Done;
+            eventlog.push(GCEvent::RunFinalizer { addr });
+        }
+    }
+    eventlog
+}
+
+/// The event of reclaiming the object at `addr`, which stands out
+/// for objects whose finalizer ran.
+pub fn reclaim_event(heap: &Heap, addr: ObjAddr) -> GCEvent {
+    let obj = heap.objects.get(&addr).unwrap();
+    let size = obj.size();
+    match obj.header.finalization {
+        Finalization::Done => GCEvent::ReclaimFinalized { addr, size },
+        _ => GCEvent::FreeObject { addr, size },
+    }
+}
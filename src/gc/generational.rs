@@ -0,0 +1,488 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::VMError,
+    free_list::FreeList,
+    heap::Heap,
+    object::{Address, Field, ObjAddr, Object},
+};
+
+use super::{
+    card_table::{scan_dirty_cards, CardTable},
+    common::clear_weak_refs,
+    ephemeron,
+    object_marker::ObjectMarker,
+    stats::{CardTableStats, GCStats},
+    GCEvent, GCType, GarbageCollector,
+};
+
+/// Generation targeted by a collection.
+/// Collecting the old generation means collecting the whole heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Generation {
+    Young,
+    Old,
+}
+
+/// Young/old split of the heap.
+/// The old generation occupies `[0, nursery_start)` and the nursery the rest
+/// of the heap. `Heap::free_list` only covers the nursery, so the mutator
+/// always allocates young objects; the old generation is filled by promotion.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Generations {
+    pub nursery_start: usize,
+    pub old_free_list: FreeList,
+    /// Addresses of old-generation fields that point into the nursery.
+    /// With a card table it is only filled in while a collection runs,
+    /// the dirty cards remember those fields in between.
+    pub remembered_set: BTreeSet<usize>,
+}
+
+impl Generations {
+    pub fn new(nursery_start: usize) -> Self {
+        let mut old_free_list = FreeList::new(vec![]);
+        if nursery_start > 0 {
+            old_free_list.insert(0, nursery_start);
+        }
+        Generations {
+            nursery_start,
+            old_free_list,
+            remembered_set: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_young(&self, addr: ObjAddr) -> bool {
+        addr >= self.nursery_start
+    }
+
+    /// Write barrier, invoked on every pointer store of `target` into `slot`.
+    pub fn record_write(&mut self, slot: usize, target: ObjAddr) {
+        if self.is_young(slot) {
+            return;
+        }
+        if self.is_young(target) {
+            self.remembered_set.insert(slot);
+        } else {
+            self.remembered_set.remove(&slot);
+        }
+    }
+}
+
+pub struct Generational {
+    nursery_size: Option<usize>,
+    promotion_age: u8,
+    card_size: Option<usize>,
+}
+
+impl ObjectMarker for Generational {}
+
+impl Generational {
+    pub fn new(nursery_size: Option<usize>, promotion_age: u8, card_size: Option<usize>) -> Self {
+        Generational {
+            nursery_size,
+            promotion_age,
+            card_size,
+        }
+    }
+
+    /// A minor collection is only safe if every object that is old enough
+    /// could be promoted. Otherwise collect the whole heap first.
+    fn needs_major(&self, heap: &Heap, gens: &Generations) -> bool {
+        let promotable: usize = heap
+            .objects
+            .range(gens.nursery_start..)
+            .filter(|(_, obj)| obj.header.age.saturating_add(1) >= self.promotion_age)
+            .map(|(_, obj)| obj.size())
+            .sum();
+        let old_free: usize = gens.old_free_list.iter().map(|(_, size)| size).sum();
+        promotable > old_free
+    }
+
+    fn minor(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>, stats: &mut GCStats) {
+        eventlog.push(GCEvent::CollectGeneration {
+            generation: Generation::Young,
+        });
+        if let Some(cards) = stats.card_table.as_mut() {
+            eventlog.push(GCEvent::phase("scan dirty cards".to_string()));
+            cards.scanned = scan_remembered_cards(heap, eventlog);
+        }
+        eventlog.push(GCEvent::phase("Mark nursery".to_string()));
+        self.mark_nursery(heap, eventlog);
+        self.mark_nursery_ephemerons(heap, eventlog);
+
+        eventlog.push(GCEvent::phase("sweep nursery".to_string()));
+        self.sweep(heap, eventlog, Generation::Young);
+
+        self.promote(heap, eventlog);
+    }
+
+    fn major(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        eventlog.push(GCEvent::CollectGeneration {
+            generation: Generation::Old,
+        });
+        eventlog.push(GCEvent::phase("Mark from roots".to_string()));
+        self.mark_from_roots(heap, eventlog);
+        self.mark_ephemerons(heap, eventlog);
+
+        eventlog.push(GCEvent::phase("sweep".to_string()));
+        self.sweep(heap, eventlog, Generation::Old);
+        rebuild_remembered_set(heap);
+
+        self.promote(heap, eventlog);
+    }
+
+    /// Marks the nursery only. Young roots and the targets of remembered
+    /// old-to-young pointers are the starting points; tracing stops at the
+    /// old generation boundary.
+    fn mark_nursery(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        let gens = heap.generations.as_ref().unwrap();
+        let nursery_start = gens.nursery_start;
+
+        let mut stack: Vec<ObjAddr> = gens
+            .remembered_set
+            .iter()
+            .filter_map(|slot| match heap.field_at(*slot) {
+                Some(Field::Ref {
+                    addr: Address::Ptr(target),
+                }) => Some(*target),
+                _ => None,
+            })
+            .collect();
+        stack.extend(heap.roots.range(nursery_start..).rev());
+
+        for (_, obj) in heap.objects.range_mut(nursery_start..) {
+            obj.header.marked = false;
+        }
+        self.mark_young(heap, eventlog, stack);
+    }
+
+    /// Marks the values of ephemerons whose keys are old or marked, until
+    /// no more values become reachable. The old generation is all live
+    /// as far as a minor collection is concerned.
+    fn mark_nursery_ephemerons(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        if !ephemeron::any(heap) {
+            return;
+        }
+        let nursery_start = heap.generations.as_ref().unwrap().nursery_start;
+        for iteration in 1.. {
+            let ready = ephemeron::ready_values(heap, |heap, addr| {
+                addr < nursery_start || heap.objects[&addr].header.marked
+            });
+            eventlog.push(GCEvent::EphemeronIteration {
+                iteration,
+                ready: ready.len(),
+            });
+            if ready.is_empty() {
+                break;
+            }
+            self.mark_young(heap, eventlog, ready);
+        }
+    }
+
+    /// Marks the nursery objects reachable from `stack`.
+    fn mark_young(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>, mut stack: Vec<ObjAddr>) {
+        let nursery_start = heap.generations.as_ref().unwrap().nursery_start;
+        while let Some(addr) = stack.pop() {
+            if addr < nursery_start {
+                continue;
+            }
+            if let Some(obj) = heap.objects.get_mut(&addr) {
+                if obj.header.marked {
+                    continue;
+                }
+                obj.header.marked = true;
+                eventlog.push(GCEvent::MarkObject {
+                    addr,
+                    size: obj.size(),
+                    worker: None,
+                });
+
+                for field in &obj.fields {
+                    if let Field::Ref {
+                        addr: Address::Ptr(a),
+                    } = field
+                    {
+                        stack.push(*a);
+                    }
+                }
+            }
+        }
+    }
+
+    fn sweep(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>, generation: Generation) {
+        let nursery_start = heap.generations.as_ref().unwrap().nursery_start;
+        let from = match generation {
+            Generation::Young => nursery_start,
+            Generation::Old => 0,
+        };
+
+        // A minor collection leaves the old generation alone
+        clear_weak_refs(heap, eventlog, |heap, addr| {
+            addr < from || heap.objects[&addr].header.marked
+        });
+
+        let garbage: Vec<ObjAddr> = heap
+            .objects
+            .range(from..)
+            .filter(|(_, obj)| !obj.header.marked)
+            .map(|(addr, _)| *addr)
+            .collect();
+
+        for addr in garbage {
+            let size = heap.objects.get(&addr).unwrap().size();
+            if addr >= nursery_start {
+                if heap.free_object(addr).is_err() {
+                    panic!("sweep:free_object at {addr:}");
+                }
+            } else {
+                heap.objects.remove(&addr);
+                heap.roots.remove(&addr);
+                let gens = heap.generations.as_mut().unwrap();
+                gens.old_free_list.insert(addr, size);
+            }
+            eventlog.push(GCEvent::FreeObject { addr, size });
+        }
+        // Promotion allocates first-fit, so neighbouring dead objects have
+        // to form a single block
+        let gens = heap.generations.as_mut().unwrap();
+        gens.old_free_list.merge_adjacent_blocks();
+    }
+
+    /// Ages every nursery survivor and moves the ones that reached
+    /// `promotion_age` into the old generation.
+    fn promote(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        eventlog.push(GCEvent::phase("promote".to_string()));
+        let nursery_start = heap.generations.as_ref().unwrap().nursery_start;
+        let survivors: Vec<ObjAddr> = heap
+            .objects
+            .range(nursery_start..)
+            .map(|(addr, _)| *addr)
+            .collect();
+
+        // promoted object start -> (new address, size)
+        let mut forwarding: BTreeMap<ObjAddr, (ObjAddr, usize)> = BTreeMap::new();
+
+        for from in survivors {
+            let obj = heap.objects.get_mut(&from).unwrap();
+            obj.header.age = obj.header.age.saturating_add(1);
+            if obj.header.age < self.promotion_age {
+                continue;
+            }
+            let size = obj.size();
+            if let Some(to) = allocate_old(heap, size) {
+                if let Err(e) = heap.move_object(from, to) {
+                    panic!("promote {e}");
+                }
+                forwarding.insert(from, (to, size));
+                eventlog.push(GCEvent::PromoteObject { from, to, size });
+            }
+        }
+
+        if forwarding.is_empty() {
+            return;
+        }
+
+        eventlog.push(GCEvent::phase("update references".to_string()));
+        update_references(heap, eventlog, &forwarding);
+
+        // Promoted objects may still point into the nursery, and remembered
+        // slots that pointed to a promoted object are old-to-old now.
+        let gens = heap.generations.as_ref().unwrap();
+        let mut remembered_set: BTreeSet<usize> = gens
+            .remembered_set
+            .iter()
+            .filter(|slot| points_into_nursery(heap.field_at(**slot), nursery_start))
+            .cloned()
+            .collect();
+        for (to, _) in forwarding.values() {
+            let obj = heap.objects.get(to).unwrap();
+            remembered_set.extend(young_slots(*to, obj, nursery_start));
+        }
+        heap.generations.as_mut().unwrap().remembered_set = remembered_set;
+    }
+}
+
+/// Fills the remembered set from the pointers into the nursery found on
+/// dirty cards of the old generation. Returns the number of cards scanned.
+fn scan_remembered_cards(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) -> usize {
+    let nursery_start = heap.generations.as_ref().unwrap().nursery_start;
+    let (slots, scanned) = scan_dirty_cards(heap, eventlog, nursery_start);
+    let remembered_set: BTreeSet<usize> = slots
+        .into_iter()
+        .filter(|slot| points_into_nursery(heap.field_at(*slot), nursery_start))
+        .collect();
+    heap.generations.as_mut().unwrap().remembered_set = remembered_set;
+    scanned
+}
+
+/// Hands the remembered set over to the card table once a collection is
+/// done: only the cards of old-to-young pointers that survived stay dirty.
+fn summarize_into_cards(heap: &mut Heap) {
+    let Some(cards) = heap.card_table.as_mut() else {
+        return;
+    };
+    let gens = heap.generations.as_mut().unwrap();
+    cards.clear();
+    for slot in std::mem::take(&mut gens.remembered_set) {
+        cards.dirty(slot);
+    }
+    cards.dirtied = 0;
+}
+
+fn points_into_nursery(field: Option<&Field>, nursery_start: usize) -> bool {
+    matches!(field, Some(Field::Ref { addr: Address::Ptr(target) }) if *target >= nursery_start)
+}
+
+/// Field addresses of `obj` that hold pointers into the nursery.
+fn young_slots(obj_addr: ObjAddr, obj: &Object, nursery_start: usize) -> Vec<usize> {
+    obj.fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| points_into_nursery(Some(field), nursery_start))
+        .map(|(i, _)| obj.field_addr(obj_addr, i))
+        .collect()
+}
+
+fn rebuild_remembered_set(heap: &mut Heap) {
+    let nursery_start = heap.generations.as_ref().unwrap().nursery_start;
+    let remembered_set: BTreeSet<usize> = heap
+        .objects
+        .range(..nursery_start)
+        .flat_map(|(addr, obj)| young_slots(*addr, obj, nursery_start))
+        .collect();
+    heap.generations.as_mut().unwrap().remembered_set = remembered_set;
+}
+
+/// First-fit allocation in the old generation, used for promotion only.
+fn allocate_old(heap: &mut Heap, size: usize) -> Option<ObjAddr> {
+    let blocks = heap.generations.as_ref().unwrap().old_free_list.to_vec();
+    for (block_start, block_size) in blocks {
+        let aligned_start = heap.aligned_position(block_start);
+        let block_end = aligned_start + size;
+        if block_end <= block_start + block_size {
+            let old_free_list = &mut heap.generations.as_mut().unwrap().old_free_list;
+            old_free_list.remove(block_start);
+            if aligned_start > block_start {
+                old_free_list.insert(block_start, aligned_start - block_start);
+            }
+            if block_start + block_size > block_end {
+                old_free_list.insert(block_end, block_start + block_size - block_end);
+            }
+            return Some(aligned_start);
+        }
+    }
+    None
+}
+
+/// Rewrites pointers to promoted objects. Only nursery objects, the promoted
+/// objects themselves and remembered old slots can hold such pointers.
+fn update_references(
+    heap: &mut Heap,
+    eventlog: &mut Vec<GCEvent>,
+    forwarding: &BTreeMap<ObjAddr, (ObjAddr, usize)>,
+) {
+    let gens = heap.generations.as_ref().unwrap();
+    let mut slots: Vec<usize> = gens.remembered_set.iter().cloned().collect();
+    for (addr, obj) in heap.objects.range(gens.nursery_start..) {
+        slots.extend(*addr..*addr + obj.size());
+    }
+    for (to, size) in forwarding.values() {
+        slots.extend(*to..*to + *size);
+    }
+
+    let forward = |ptr: ObjAddr| -> Option<ObjAddr> {
+        let (from, (to, size)) = forwarding.range(..=ptr).next_back()?;
+        (ptr < from + size).then(|| to + (ptr - from))
+    };
+
+    for slot in slots {
+        let Ok(obj_addr) = heap.lookup_object_addr(slot) else {
+            continue;
+        };
+        let obj = heap.objects.get_mut(&obj_addr).unwrap();
+        if let Some(Field::Ref {
+            addr: Address::Ptr(old),
+        }) = obj.field_mut(slot - obj_addr)
+        {
+            if let Some(new) = forward(*old) {
+                eventlog.push(GCEvent::UpdateFwdPtr { old: *old, new });
+                *old = new;
+            }
+        }
+    }
+
+    // Weak references and ephemerons are not remembered,
+    // any object may hold one
+    for obj in heap.objects.values_mut() {
+        for field in obj.fields.iter_mut() {
+            if let Field::WeakRef {
+                addr: Address::Ptr(old),
+            }
+            | Field::EphemeronKey {
+                addr: Address::Ptr(old),
+            }
+            | Field::EphemeronValue {
+                addr: Address::Ptr(old),
+            } = field
+            {
+                if let Some(new) = forward(*old) {
+                    eventlog.push(GCEvent::UpdateFwdPtr { old: *old, new });
+                    *old = new;
+                }
+            }
+        }
+    }
+}
+
+impl GarbageCollector for Generational {
+    fn collect(&mut self, heap: &mut Heap) -> Result<(GCStats, Vec<GCEvent>), VMError> {
+        let Some(gens) = heap.generations.as_ref() else {
+            return Err(VMError::GCError);
+        };
+        let mut eventlog = vec![GCEvent::phase("Generational: START".to_string())];
+        let mut stats = GCStats::new();
+        stats.card_table = heap.card_table.as_ref().map(|cards| CardTableStats {
+            dirtied: cards.dirtied,
+            scanned: 0,
+        });
+
+        if self.needs_major(heap, gens) {
+            self.major(heap, &mut eventlog);
+        } else {
+            self.minor(heap, &mut eventlog, &mut stats);
+        }
+        summarize_into_cards(heap);
+
+        eventlog.push(GCEvent::phase("Generational: END".to_string()));
+        Ok((stats, eventlog))
+    }
+
+    fn init_heap(&mut self, heap: &mut Heap) {
+        let heap_size = heap.memory.len();
+        let nursery_size = self.nursery_size.unwrap_or(heap_size / 4).min(heap_size);
+        let nursery_start = heap_size - nursery_size;
+
+        heap.free_list = FreeList::new(vec![]);
+        if nursery_size > 0 {
+            heap.free_list.insert(nursery_start, nursery_size);
+        }
+        heap.generations = Some(Generations::new(nursery_start));
+        heap.card_table = self
+            .card_size
+            .map(|card_size| CardTable::new(heap_size, card_size));
+    }
+
+    fn ty(&self) -> GCType {
+        GCType::Generational
+    }
+
+    fn new_instance(&self) -> Box<dyn GarbageCollector> {
+        Box::new(Generational::new(
+            self.nursery_size,
+            self.promotion_age,
+            self.card_size,
+        ))
+    }
+}
@@ -0,0 +1,471 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::VMError,
+    heap::Heap,
+    object::{Address, Field, ObjAddr},
+};
+
+use super::{
+    common::{clear_weak_refs, evacuate, forward_weak_refs, rebuild_free_list},
+    ephemeron,
+    stats::GCStats,
+    GCEvent, GCType, GarbageCollector,
+};
+
+/// Number of heap words in a line.
+const LINE_SIZE: usize = 4;
+/// Number of lines in a block.
+const LINES_PER_BLOCK: usize = 4;
+/// Blocks whose free lines are split into at least this many holes
+/// are evacuated during the next collection.
+const FRAGMENTED_HOLES: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockState {
+    /// No line is live.
+    Free,
+    /// Some lines are live, the rest can be allocated into.
+    Recyclable,
+    /// Every line is live.
+    Unavailable,
+}
+
+/// Block and line layout of the heap.
+/// `line_marks` and `block_states` describe the heap as of the last
+/// collection. The allocator bumps `cursor` towards `limit` through the
+/// hole it is currently filling, then moves on to the next run of unmarked
+/// lines. Holes never span blocks, and holes behind the cursor are left
+/// alone until the next collection. Objects larger than a line that fit no
+/// hole are overflow-allocated from the free list instead, and may span
+/// blocks.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Regions {
+    pub line_size: usize,
+    pub block_size: usize,
+    pub line_marks: Vec<bool>,
+    pub block_states: Vec<BlockState>,
+    pub cursor: usize,
+    pub limit: usize,
+}
+
+impl Regions {
+    pub fn new(heap_size: usize) -> Self {
+        let block_size = LINE_SIZE * LINES_PER_BLOCK;
+        Regions {
+            line_size: LINE_SIZE,
+            block_size,
+            line_marks: vec![false; heap_size.div_ceil(LINE_SIZE)],
+            block_states: vec![BlockState::Free; heap_size.div_ceil(block_size)],
+            cursor: 0,
+            limit: 0,
+        }
+    }
+
+    fn lines_per_block(&self) -> usize {
+        self.block_size / self.line_size
+    }
+
+    /// Lines covered by the words `[start, end)`.
+    fn lines(&self, start: usize, end: usize) -> std::ops::Range<usize> {
+        start / self.line_size..end.div_ceil(self.line_size)
+    }
+
+    /// Finds the first run of unmarked lines at or after `from`.
+    fn next_hole(&self, from: usize, heap_size: usize) -> Option<(usize, usize)> {
+        let lines = self.line_marks.len();
+        let first = (from.div_ceil(self.line_size)..lines).find(|l| !self.line_marks[*l])?;
+        let block_end = (first / self.lines_per_block() + 1) * self.lines_per_block();
+        let last = (first..block_end.min(lines))
+            .take_while(|l| !self.line_marks[*l])
+            .last()
+            .unwrap();
+        Some((
+            first * self.line_size,
+            ((last + 1) * self.line_size).min(heap_size),
+        ))
+    }
+}
+
+/// Finds room for `size` words in the current hole, moving on to the next
+/// hole when it does not fit. The cursor is only moved past the object by
+/// `bump`, once the allocation has succeeded.
+pub fn find_room(heap: &mut Heap, size: usize) -> Option<ObjAddr> {
+    let heap_size = heap.memory.len();
+    loop {
+        let regions = heap.regions.as_ref()?;
+        if let Some(start) = fit_in_hole(heap, regions.cursor, regions.limit, size) {
+            return Some(start);
+        }
+        let (hole_start, hole_end) = regions.next_hole(regions.limit, heap_size)?;
+        let regions = heap.regions.as_mut()?;
+        regions.cursor = hole_start;
+        regions.limit = hole_end;
+    }
+}
+
+/// Moves the cursor past an object allocated up to `end`.
+pub fn bump(heap: &mut Heap, end: usize) {
+    if let Some(regions) = heap.regions.as_mut() {
+        regions.cursor = end;
+    }
+}
+
+/// First address in `[cursor, limit)` where `size` free words start.
+/// Overflow allocations may have taken parts of a hole already.
+fn fit_in_hole(heap: &Heap, cursor: usize, limit: usize, size: usize) -> Option<ObjAddr> {
+    heap.free_list.iter().find_map(|(block_start, block_size)| {
+        let start = heap.aligned_position(cursor.max(*block_start));
+        (start + size <= limit.min(block_start + block_size)).then_some(start)
+    })
+}
+
+// Mark-region collection,
+// introduced by Blackburn and McKinley as Immix in 2008.
+// The heap is divided into blocks of fixed-size lines. Tracing marks
+// objects and the lines they occupy, and instead of sweeping objects into
+// a free list, the allocator bumps through holes of unmarked lines.
+// Blocks fragmented into many small holes are defragmented opportunistically:
+// objects found in them during the trace are evacuated into free lines of
+// other blocks, as long as there is room.
+pub struct Immix {}
+
+impl Default for Immix {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Immix {
+    pub fn new() -> Self {
+        Immix {}
+    }
+
+    /// Lines holding any object, live or not, before the trace.
+    fn occupied_lines(heap: &Heap, regions: &Regions) -> Vec<bool> {
+        let mut occupied = vec![false; regions.line_marks.len()];
+        for (addr, obj) in &heap.objects {
+            for line in regions.lines(*addr, addr + obj.size()) {
+                occupied[line] = true;
+            }
+        }
+        occupied
+    }
+
+    fn select_candidates(
+        &self,
+        heap: &Heap,
+        occupied: &[bool],
+        eventlog: &mut Vec<GCEvent>,
+    ) -> BTreeSet<usize> {
+        eventlog.push(GCEvent::phase("select evacuation candidates".to_string()));
+        let regions = heap.regions.as_ref().unwrap();
+        occupied
+            .chunks(regions.lines_per_block())
+            .enumerate()
+            .filter(|(_, lines)| {
+                let holes = lines
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, occ)| !**occ && (*i == 0 || lines[i - 1]))
+                    .count();
+                lines.iter().any(|occ| *occ) && holes >= FRAGMENTED_HOLES
+            })
+            .map(|(block, _)| block)
+            .collect()
+    }
+
+    fn trace(
+        &self,
+        heap: &mut Heap,
+        eventlog: &mut Vec<GCEvent>,
+        candidates: &BTreeSet<usize>,
+        occupied: &[bool],
+    ) {
+        eventlog.push(GCEvent::phase("trace".to_string()));
+        for obj in heap.objects.values_mut() {
+            obj.header.marked = false;
+        }
+
+        let regions = heap.regions.as_ref().unwrap();
+        let mut evacuation = EvacuationSpace::new(regions, candidates, occupied, heap);
+        let mut stack = vec![];
+
+        let roots: Vec<ObjAddr> = heap.roots.iter().cloned().collect();
+        for root in roots {
+            // Evacuated roots are moved in the root set by `evacuate`
+            self.visit(
+                heap,
+                eventlog,
+                candidates,
+                &mut evacuation,
+                &mut stack,
+                root,
+            );
+        }
+
+        self.scan_stack(heap, eventlog, candidates, &mut evacuation, &mut stack);
+
+        // Values of ephemerons whose keys were reached, until no more are
+        if !ephemeron::any(heap) {
+            return;
+        }
+        for iteration in 1.. {
+            let ready = ephemeron::ready_values(heap, survives);
+            eventlog.push(GCEvent::EphemeronIteration {
+                iteration,
+                ready: ready.len(),
+            });
+            if ready.is_empty() {
+                break;
+            }
+            for addr in ready {
+                self.visit(
+                    heap,
+                    eventlog,
+                    candidates,
+                    &mut evacuation,
+                    &mut stack,
+                    addr,
+                );
+            }
+            self.scan_stack(heap, eventlog, candidates, &mut evacuation, &mut stack);
+        }
+    }
+
+    /// Marks everything reachable from the objects on `stack`.
+    fn scan_stack(
+        &self,
+        heap: &mut Heap,
+        eventlog: &mut Vec<GCEvent>,
+        candidates: &BTreeSet<usize>,
+        evacuation: &mut EvacuationSpace,
+        stack: &mut Vec<ObjAddr>,
+    ) {
+        while let Some(addr) = stack.pop() {
+            let refs: Vec<(usize, ObjAddr)> = heap
+                .objects
+                .get(&addr)
+                .unwrap()
+                .fields
+                .iter()
+                .enumerate()
+                .filter_map(|(i, field)| match field {
+                    Field::Ref {
+                        addr: Address::Ptr(ptr),
+                    } => Some((i, *ptr)),
+                    _ => None,
+                })
+                .collect();
+
+            for (i, old) in refs {
+                let new = self.visit(heap, eventlog, candidates, evacuation, stack, old);
+                if let Some(new) = new {
+                    heap.objects.get_mut(&addr).unwrap().fields[i] =
+                        Field::new_ref(Address::Ptr(new));
+                    eventlog.push(GCEvent::UpdateFwdPtr { old, new });
+                }
+            }
+        }
+    }
+
+    /// Marks the object `ptr` points into, evacuating it first if it sits
+    /// in a candidate block. Returns the address `ptr` has to be rewritten
+    /// to if the object has moved.
+    fn visit(
+        &self,
+        heap: &mut Heap,
+        eventlog: &mut Vec<GCEvent>,
+        candidates: &BTreeSet<usize>,
+        evacuation: &mut EvacuationSpace,
+        stack: &mut Vec<ObjAddr>,
+        ptr: ObjAddr,
+    ) -> Option<ObjAddr> {
+        let obj_addr = heap.lookup_object_addr(ptr).ok()?;
+        let offset = ptr - obj_addr;
+        let obj = heap.objects.get(&obj_addr)?;
+
+        if let Some(fwd_addr) = obj.header.fwd_addr {
+            return Some(fwd_addr + offset);
+        }
+        if obj.header.marked {
+            return None;
+        }
+
+        let size = obj.size();
+        let block = obj_addr / heap.regions.as_ref().unwrap().block_size;
+        // Pinned objects are marked in place, even in a candidate block
+        let to = if !candidates.contains(&block) {
+            None
+        } else if obj.header.pinned {
+            eventlog.push(GCEvent::SkipPinned {
+                addr: obj_addr,
+                size,
+            });
+            None
+        } else {
+            evacuation.allocate(heap, size)
+        };
+
+        let addr = to.unwrap_or(obj_addr);
+        if let Some(to) = to {
+            evacuate(heap, eventlog, obj_addr, to);
+        }
+        heap.objects.get_mut(&addr).unwrap().header.marked = true;
+        eventlog.push(GCEvent::MarkObject {
+            addr,
+            size,
+            worker: None,
+        });
+        stack.push(addr);
+        to.map(|to| to + offset)
+    }
+
+    /// Forwards the weak references and ephemerons of marked objects whose
+    /// targets were evacuated, and clears the ones whose targets are unmarked.
+    fn update_weak_refs(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        clear_weak_refs(heap, eventlog, survives);
+        forward_weak_refs(heap, eventlog);
+    }
+
+    fn mark_lines(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        eventlog.push(GCEvent::phase("mark lines".to_string()));
+        let regions = heap.regions.as_mut().unwrap();
+        regions.line_marks.fill(false);
+        for (addr, obj) in &heap.objects {
+            if !obj.header.marked {
+                continue;
+            }
+            for line in regions.lines(*addr, addr + obj.size()) {
+                if !regions.line_marks[line] {
+                    regions.line_marks[line] = true;
+                    eventlog.push(GCEvent::MarkLine { line });
+                }
+            }
+        }
+    }
+
+    /// Drops unmarked objects and forwarding stubs, then recomputes
+    /// the block states and restarts allocation from the first hole.
+    fn sweep(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        eventlog.push(GCEvent::phase("sweep".to_string()));
+        let unmarked: Vec<ObjAddr> = heap
+            .objects
+            .iter()
+            .filter(|(_, obj)| !obj.header.marked)
+            .map(|(addr, _)| *addr)
+            .collect();
+        for addr in unmarked {
+            let obj = heap.objects.remove(&addr).unwrap();
+            if obj.header.fwd_addr.is_none() {
+                heap.roots.remove(&addr);
+                eventlog.push(GCEvent::FreeObject {
+                    addr,
+                    size: obj.size(),
+                });
+            }
+        }
+        rebuild_free_list(heap);
+
+        let regions = heap.regions.as_mut().unwrap();
+        let lines_per_block = regions.lines_per_block();
+        regions.block_states = regions
+            .line_marks
+            .chunks(lines_per_block)
+            .map(|lines| {
+                if lines.iter().all(|marked| *marked) {
+                    BlockState::Unavailable
+                } else if lines.iter().any(|marked| *marked) {
+                    BlockState::Recyclable
+                } else {
+                    BlockState::Free
+                }
+            })
+            .collect();
+        regions.cursor = 0;
+        regions.limit = 0;
+    }
+}
+
+/// Whether the object at `addr` was marked or evacuated by the trace.
+fn survives(heap: &Heap, addr: ObjAddr) -> bool {
+    let obj = &heap.objects[&addr];
+    obj.header.marked || obj.header.fwd_addr.is_some()
+}
+
+/// Free lines outside of the candidate blocks, bump-allocated
+/// into by evacuated objects.
+struct EvacuationSpace {
+    holes: Vec<(usize, usize)>,
+    cursor: usize,
+}
+
+impl EvacuationSpace {
+    fn new(
+        regions: &Regions,
+        candidates: &BTreeSet<usize>,
+        occupied: &[bool],
+        heap: &Heap,
+    ) -> Self {
+        let lines_per_block = regions.lines_per_block();
+        let mut holes: Vec<(usize, usize)> = vec![];
+        for (line, occ) in occupied.iter().enumerate() {
+            if *occ || candidates.contains(&(line / lines_per_block)) {
+                continue;
+            }
+            let start = line * regions.line_size;
+            let end = (start + regions.line_size).min(heap.memory.len());
+            match holes.last_mut() {
+                // Extend the previous hole unless a block boundary lies in between
+                Some((_, hole_end)) if *hole_end == start && line % lines_per_block != 0 => {
+                    *hole_end = end;
+                }
+                _ => holes.push((start, end)),
+            }
+        }
+        let cursor = holes.first().map_or(0, |(start, _)| *start);
+        EvacuationSpace { holes, cursor }
+    }
+
+    fn allocate(&mut self, heap: &Heap, size: usize) -> Option<ObjAddr> {
+        while let Some((start, end)) = self.holes.first().copied() {
+            let addr = heap.aligned_position(self.cursor.max(start));
+            if addr + size <= end {
+                self.cursor = addr + size;
+                return Some(addr);
+            }
+            self.holes.remove(0);
+        }
+        None
+    }
+}
+
+impl GarbageCollector for Immix {
+    fn collect(&mut self, heap: &mut Heap) -> Result<(GCStats, Vec<GCEvent>), VMError> {
+        let mut eventlog = vec![GCEvent::phase("Immix: START".to_string())];
+
+        let occupied = Self::occupied_lines(heap, heap.regions.as_ref().unwrap());
+        let candidates = self.select_candidates(heap, &occupied, &mut eventlog);
+        self.trace(heap, &mut eventlog, &candidates, &occupied);
+        self.update_weak_refs(heap, &mut eventlog);
+        self.mark_lines(heap, &mut eventlog);
+        self.sweep(heap, &mut eventlog);
+
+        eventlog.push(GCEvent::phase("Immix: END".to_string()));
+        Ok((GCStats::new(), eventlog))
+    }
+
+    fn init_heap(&mut self, heap: &mut Heap) {
+        heap.regions = Some(Regions::new(heap.memory.len()));
+    }
+
+    fn ty(&self) -> GCType {
+        GCType::Immix
+    }
+
+    fn new_instance(&self) -> Box<dyn GarbageCollector> {
+        Box::new(Immix::new())
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{free_list::FreeList, heap::Heap, object::ObjAddr};
+
+use super::{finalization::reclaim_event, GCEvent};
+
+/// Number of heap words in a page of the large object space.
+const PAGE_SIZE: usize = 4;
+
+/// Space at the end of the heap set aside for objects of at least
+/// `threshold` words. It is managed page by page: every large object gets a
+/// run of whole pages to itself. The space is not covered by
+/// `Heap::free_list` and its objects are never moved by compaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LargeObjectSpace {
+    pub threshold: usize,
+    pub page_size: usize,
+    /// First heap word of the space, which extends to the end of the heap.
+    pub start: usize,
+    /// Object occupying each page, if any.
+    pub pages: Vec<Option<ObjAddr>>,
+}
+
+impl LargeObjectSpace {
+    /// Reserves the last quarter of the heap, rounded down to whole pages.
+    pub fn new(heap_size: usize, threshold: usize) -> Self {
+        let num_pages = heap_size / 4 / PAGE_SIZE;
+        LargeObjectSpace {
+            threshold,
+            page_size: PAGE_SIZE,
+            start: heap_size - num_pages * PAGE_SIZE,
+            pages: vec![None; num_pages],
+        }
+    }
+
+    pub fn contains(&self, addr: ObjAddr) -> bool {
+        addr >= self.start
+    }
+
+    pub fn is_large(&self, size: usize) -> bool {
+        size >= self.threshold
+    }
+
+    pub fn used_pages(&self) -> usize {
+        self.pages.iter().filter(|page| page.is_some()).count()
+    }
+
+    /// Gives the pages of the object at `addr` back to the space.
+    pub fn release(&mut self, addr: ObjAddr) {
+        for page in self.pages.iter_mut() {
+            if *page == Some(addr) {
+                *page = None;
+            }
+        }
+    }
+}
+
+/// Sets the large object space aside on a fresh heap,
+/// the free list only covers the memory below it.
+pub fn reserve(heap: &mut Heap, threshold: Option<usize>) {
+    let Some(threshold) = threshold else {
+        return;
+    };
+    let los = LargeObjectSpace::new(heap.memory.len(), threshold);
+    heap.free_list = FreeList::new(vec![]);
+    if los.start > 0 {
+        heap.free_list.insert(0, los.start);
+    }
+    heap.large_objects = Some(los);
+}
+
+/// Takes the first run of free pages that fits an object of `size` words.
+pub fn allocate(heap: &mut Heap, size: usize) -> Option<ObjAddr> {
+    let los = heap.large_objects.as_mut()?;
+    let num_pages = size.div_ceil(los.page_size).max(1);
+    let first = los
+        .pages
+        .windows(num_pages)
+        .position(|run| run.iter().all(Option::is_none))?;
+    let addr = los.start + first * los.page_size;
+    los.pages[first..first + num_pages].fill(Some(addr));
+    Some(addr)
+}
+
+/// Frees the large objects that `is_live` rejects. They never move, so
+/// the space is swept as a whole rather than compacted.
+pub fn sweep(
+    heap: &mut Heap,
+    eventlog: &mut Vec<GCEvent>,
+    is_live: impl Fn(&Heap, ObjAddr) -> bool,
+) {
+    let Some(start) = heap.large_objects.as_ref().map(|los| los.start) else {
+        return;
+    };
+    let dead: Vec<ObjAddr> = heap
+        .objects
+        .range(start..)
+        .map(|(addr, _)| *addr)
+        .filter(|addr| !is_live(heap, *addr))
+        .collect();
+    if dead.is_empty() {
+        return;
+    }
+
+    eventlog.push(GCEvent::phase("sweep large objects".to_string()));
+    for addr in dead {
+        let event = reclaim_event(heap, addr);
+        match heap.free_object(addr) {
+            Ok(_) => eventlog.push(event),
+            Err(_e) => panic!("sweep:free_object at {addr:}"),
+        }
+    }
+}
@@ -3,32 +3,61 @@ use serde::{Deserialize, Serialize};
 use crate::{error::VMError, heap::Heap};
 
 use super::{
-    compact, object_marker::ObjectMarker, stats::GCStats, GCEvent, GCType, GarbageCollector,
+    compact, large_object_space,
+    object_marker::{reserve_mark_bitmap, ObjectMarker},
+    stats::GCStats,
+    GCEvent, GCType, GarbageCollector, SideTables,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CompactAlgorithm {
     TwoFinger,
     Lisp2,
+    Jonkers,
+    Compressor,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarkCompact {
     algo: CompactAlgorithm,
+    #[serde(skip)]
+    side_tables: Option<SideTables>,
+    large_object_threshold: Option<usize>,
+    /// Simulated GC threads marking in parallel, if any.
+    workers: Option<usize>,
+    /// Granularity of the heap's mark bitmap, if marks are kept in one.
+    mark_bitmap: Option<usize>,
 }
 
 impl Default for MarkCompact {
     fn default() -> Self {
-        Self::new(CompactAlgorithm::TwoFinger)
+        Self::new(CompactAlgorithm::TwoFinger, None, None, None)
     }
 }
 
 impl MarkCompact {
-    pub fn new(compact: CompactAlgorithm) -> Self {
-        MarkCompact { algo: compact }
+    pub fn new(
+        compact: CompactAlgorithm,
+        large_object_threshold: Option<usize>,
+        workers: Option<usize>,
+        mark_bitmap: Option<usize>,
+    ) -> Self {
+        MarkCompact {
+            algo: compact,
+            side_tables: None,
+            large_object_threshold,
+            workers,
+            mark_bitmap,
+        }
+    }
+
+    /// Compressor keeps liveness in a bitmap of its own instead of
+    /// object headers or the heap's mark bitmap.
+    fn marks_headers(&self) -> bool {
+        !matches!(self.algo, CompactAlgorithm::Compressor)
     }
 
-    fn compact(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+    fn compact(&mut self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
         match self.algo {
             CompactAlgorithm::TwoFinger => {
                 compact::two_finger::compact(heap, eventlog);
@@ -36,6 +65,12 @@ impl MarkCompact {
             CompactAlgorithm::Lisp2 => {
                 compact::lisp_2::compact(heap, eventlog);
             }
+            CompactAlgorithm::Jonkers => {
+                compact::jonkers::compact(heap, eventlog);
+            }
+            CompactAlgorithm::Compressor => {
+                self.side_tables = Some(compact::compressor::compact(heap, eventlog));
+            }
         }
     }
 }
@@ -43,17 +78,38 @@ impl MarkCompact {
 impl ObjectMarker for MarkCompact {}
 
 impl GarbageCollector for MarkCompact {
-    fn collect(&self, heap: &mut Heap) -> Result<(GCStats, Vec<GCEvent>), VMError> {
+    fn collect(&mut self, heap: &mut Heap) -> Result<(GCStats, Vec<GCEvent>), VMError> {
         let mut eventlog = vec![GCEvent::phase("MarkCompact: START".to_string())];
-        eventlog.push(GCEvent::phase("Mark from roots".to_string()));
-
-        self.mark_from_roots(heap, &mut eventlog);
+        let mut stats = GCStats::new();
+        if self.marks_headers() {
+            eventlog.push(GCEvent::phase("Mark from roots".to_string()));
+            match self.workers {
+                Some(workers) => {
+                    stats.parallel_mark =
+                        Some(self.parallel_mark_from_roots(heap, &mut eventlog, workers));
+                }
+                None => self.mark_from_roots(heap, &mut eventlog),
+            }
+            self.mark_ephemerons(heap, &mut eventlog);
+            self.resurrect_finalizable(heap, &mut eventlog);
+        }
         eventlog.push(GCEvent::phase("compact".to_string()));
 
         self.compact(heap, &mut eventlog);
         eventlog.push(GCEvent::phase("MarkCompact: END".to_string()));
 
-        Ok((GCStats::new(), eventlog))
+        Ok((stats, eventlog))
+    }
+
+    fn side_tables(&self) -> Option<SideTables> {
+        self.side_tables.clone()
+    }
+
+    fn init_heap(&mut self, heap: &mut Heap) {
+        large_object_space::reserve(heap, self.large_object_threshold);
+        if self.marks_headers() {
+            reserve_mark_bitmap(heap, self.mark_bitmap);
+        }
     }
 
     fn ty(&self) -> GCType {
@@ -61,6 +117,11 @@ impl GarbageCollector for MarkCompact {
     }
 
     fn new_instance(&self) -> Box<dyn GarbageCollector> {
-        Box::new(MarkCompact::new(self.algo.clone()))
+        Box::new(MarkCompact::new(
+            self.algo.clone(),
+            self.large_object_threshold,
+            self.workers,
+            self.mark_bitmap,
+        ))
     }
 }
@@ -1,43 +1,91 @@
-use crate::{error::VMError, heap::Heap};
+use crate::{error::VMError, heap::Heap, segregated_fits::SizeClasses};
 
 use super::{
-    object_marker::ObjectMarker,
+    common::clear_weak_refs,
+    finalization::reclaim_event,
+    large_object_space,
+    object_marker::{is_marked, reserve_mark_bitmap, ObjectMarker, WriteBarrier},
     stats::GCStats,
     GCEvent, {GCType, GarbageCollector},
 };
 
-pub struct MarkSweep {}
+pub struct MarkSweep {
+    /// Objects to blacken per mutator instruction. Marking is done
+    /// all at once inside `GC` when unset.
+    mark_budget: Option<usize>,
+    barrier: WriteBarrier,
+    large_object_threshold: Option<usize>,
+    /// Simulated GC threads marking in parallel, if any.
+    workers: Option<usize>,
+    /// Granularity of the heap's mark bitmap, if marks are kept in one.
+    mark_bitmap: Option<usize>,
+    /// Cell sizes of segregated-fits allocation, if any.
+    size_classes: Option<Vec<usize>>,
+}
 
 impl Default for MarkSweep {
     fn default() -> Self {
-        Self::new()
+        Self::new(None, None, None, None)
     }
 }
 
 impl ObjectMarker for MarkSweep {}
 
 impl MarkSweep {
-    pub fn new() -> Self {
-        MarkSweep {}
+    pub fn new(
+        large_object_threshold: Option<usize>,
+        workers: Option<usize>,
+        mark_bitmap: Option<usize>,
+        size_classes: Option<Vec<usize>>,
+    ) -> Self {
+        MarkSweep {
+            mark_budget: None,
+            barrier: WriteBarrier::default(),
+            large_object_threshold,
+            workers,
+            mark_bitmap,
+            size_classes,
+        }
+    }
+
+    // Incremental marking,
+    // as described by Dijkstra et al. in 1978.
+    // `GC` only shades the roots; the mark stack then lives on the heap and
+    // every following mutator instruction blackens up to `mark_budget`
+    // objects. The sweep runs in the tick that leaves no grey objects.
+    pub fn incremental(
+        mark_budget: usize,
+        barrier: WriteBarrier,
+        large_object_threshold: Option<usize>,
+        mark_bitmap: Option<usize>,
+        size_classes: Option<Vec<usize>>,
+    ) -> Self {
+        MarkSweep {
+            mark_budget: Some(mark_budget),
+            barrier,
+            large_object_threshold,
+            workers: None,
+            mark_bitmap,
+            size_classes,
+        }
     }
 
     fn sweep(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        clear_weak_refs(heap, eventlog, is_marked);
+
         let mut addresses_to_remove = Vec::new();
 
-        for (addr, obj) in &heap.objects {
-            if !obj.header.marked {
+        for addr in heap.objects.keys() {
+            if !is_marked(heap, *addr) {
                 addresses_to_remove.push(*addr);
             }
         }
 
         for addr in addresses_to_remove {
-            let obj_size = heap.objects.get(&addr).unwrap().size(); // Retrieve size before freeing the object
+            let event = reclaim_event(heap, addr); // Retrieve size before freeing the object
             match heap.free_object(addr) {
                 Ok(_) => {
-                    eventlog.push(GCEvent::FreeObject {
-                        addr,
-                        size: obj_size,
-                    });
+                    eventlog.push(event);
                 }
                 Err(_e) => panic!("sweep:free_object at {addr:}"),
             }
@@ -45,20 +93,69 @@ impl MarkSweep {
 
         heap.merge_free_ranges();
     }
+
+    fn finish_incremental(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        heap.incremental_mark = None;
+        self.mark_ephemerons(heap, eventlog);
+        self.resurrect_finalizable(heap, eventlog);
+        eventlog.push(GCEvent::phase("sweep".to_string()));
+        self.sweep(heap, eventlog);
+        eventlog.push(GCEvent::phase("MarkSweep: END".to_string()));
+    }
 }
 
 impl GarbageCollector for MarkSweep {
-    fn collect(&self, heap: &mut Heap) -> Result<(GCStats, Vec<GCEvent>), VMError> {
+    fn collect(&mut self, heap: &mut Heap) -> Result<(GCStats, Vec<GCEvent>), VMError> {
         let mut eventlog = vec![GCEvent::phase("MarkSweep: START".to_string())];
+
+        if self.mark_budget.is_some() {
+            if heap.incremental_mark.is_none() {
+                eventlog.push(GCEvent::phase("Shade roots".to_string()));
+                self.start_incremental_mark(heap, self.barrier, &mut eventlog);
+            } else {
+                // A `GC` during an incremental mark finishes it right away
+                eventlog.push(GCEvent::phase("Finish marking".to_string()));
+                self.incremental_mark_step(heap, usize::MAX, &mut eventlog);
+                self.finish_incremental(heap, &mut eventlog);
+            }
+            return Ok((GCStats::new(), eventlog));
+        }
+
         eventlog.push(GCEvent::phase("Mark from roots".to_string()));
 
-        self.mark_from_roots(heap, &mut eventlog);
+        let mut stats = GCStats::new();
+        match self.workers {
+            Some(workers) => {
+                stats.parallel_mark =
+                    Some(self.parallel_mark_from_roots(heap, &mut eventlog, workers));
+            }
+            None => self.mark_from_roots(heap, &mut eventlog),
+        }
+        self.mark_ephemerons(heap, &mut eventlog);
+        self.resurrect_finalizable(heap, &mut eventlog);
         eventlog.push(GCEvent::phase("sweep".to_string()));
 
         self.sweep(heap, &mut eventlog);
         eventlog.push(GCEvent::phase("MarkSweep: END".to_string()));
 
-        Ok((GCStats::new(), eventlog))
+        Ok((stats, eventlog))
+    }
+
+    fn step(&mut self, heap: &mut Heap) -> Vec<GCEvent> {
+        let (Some(budget), Some(_)) = (self.mark_budget, heap.incremental_mark.as_ref()) else {
+            return vec![];
+        };
+        let mut eventlog = vec![GCEvent::phase("Incremental mark".to_string())];
+        if self.incremental_mark_step(heap, budget, &mut eventlog) {
+            self.finish_incremental(heap, &mut eventlog);
+        }
+        eventlog
+    }
+
+    fn init_heap(&mut self, heap: &mut Heap) {
+        large_object_space::reserve(heap, self.large_object_threshold);
+        reserve_mark_bitmap(heap, self.mark_bitmap);
+        heap.size_classes = self.size_classes.as_deref().map(SizeClasses::new);
     }
 
     fn ty(&self) -> GCType {
@@ -66,6 +163,13 @@ impl GarbageCollector for MarkSweep {
     }
 
     fn new_instance(&self) -> Box<dyn GarbageCollector> {
-        Box::new(MarkSweep::new())
+        Box::new(MarkSweep {
+            mark_budget: self.mark_budget,
+            barrier: self.barrier,
+            large_object_threshold: self.large_object_threshold,
+            workers: self.workers,
+            mark_bitmap: self.mark_bitmap,
+            size_classes: self.size_classes.clone(),
+        })
     }
 }
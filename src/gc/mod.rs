@@ -1,23 +1,45 @@
+pub mod card_table;
 pub mod common;
 pub mod compact;
+pub mod copying;
+pub mod ephemeron;
+pub mod finalization;
+pub mod generational;
+pub mod immix;
+pub mod large_object_space;
 pub mod mark_compact;
 pub mod mark_sweep;
 pub mod object_marker;
+pub mod reference_counting;
 pub mod stats;
+pub mod treadmill;
 
 use serde::{Deserialize, Deserializer, Serialize};
 
+use std::fmt;
+
 use self::{
+    copying::Copying,
+    generational::{Generation, Generational},
+    immix::Immix,
     mark_compact::{CompactAlgorithm, MarkCompact},
     mark_sweep::MarkSweep,
+    object_marker::MarkBits,
+    reference_counting::ReferenceCounting,
     stats::GCStats,
+    treadmill::{SegmentPointer, Treadmill},
 };
-use crate::{error::VMError, heap::Heap};
+use crate::{error::VMError, heap::Heap, object::Color, rts_cfg::ProgramRuntimeConfig};
 
 #[derive(Debug, Clone)]
 pub enum GCType {
     MarkSweep,
     MarkCompact(CompactAlgorithm),
+    Copying,
+    Generational,
+    ReferenceCounting,
+    Immix,
+    Treadmill,
 }
 
 impl Serialize for GCType {
@@ -28,18 +50,25 @@ impl Serialize for GCType {
         match self {
             GCType::MarkSweep => serializer.serialize_str("MarkSweep"),
             GCType::MarkCompact(algo) => {
-                let value = format!("MarkCompact_{}", algo.to_string());
+                let value = format!("MarkCompact_{algo}");
                 serializer.serialize_str(&value)
             }
+            GCType::Copying => serializer.serialize_str("Copying"),
+            GCType::Generational => serializer.serialize_str("Generational"),
+            GCType::ReferenceCounting => serializer.serialize_str("ReferenceCounting"),
+            GCType::Immix => serializer.serialize_str("Immix"),
+            GCType::Treadmill => serializer.serialize_str("Treadmill"),
         }
     }
 }
 
-impl ToString for CompactAlgorithm {
-    fn to_string(&self) -> String {
+impl fmt::Display for CompactAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            CompactAlgorithm::TwoFinger => "TwoFinger".to_string(),
-            CompactAlgorithm::Lisp2 => "Lisp2".to_string(),
+            CompactAlgorithm::TwoFinger => write!(f, "TwoFinger"),
+            CompactAlgorithm::Lisp2 => write!(f, "Lisp2"),
+            CompactAlgorithm::Jonkers => write!(f, "Jonkers"),
+            CompactAlgorithm::Compressor => write!(f, "Compressor"),
         }
     }
 }
@@ -55,9 +84,27 @@ impl<'de> Deserialize<'de> for GCType {
             "MarkSweep" => Ok(GCType::MarkSweep),
             "MarkCompact_TwoFinger" => Ok(GCType::MarkCompact(CompactAlgorithm::TwoFinger)),
             "MarkCompact_Lisp2" => Ok(GCType::MarkCompact(CompactAlgorithm::Lisp2)),
+            "MarkCompact_Jonkers" => Ok(GCType::MarkCompact(CompactAlgorithm::Jonkers)),
+            "MarkCompact_Compressor" => Ok(GCType::MarkCompact(CompactAlgorithm::Compressor)),
+            "Copying" => Ok(GCType::Copying),
+            "Generational" => Ok(GCType::Generational),
+            "ReferenceCounting" => Ok(GCType::ReferenceCounting),
+            "Immix" => Ok(GCType::Immix),
+            "Treadmill" => Ok(GCType::Treadmill),
             _ => Err(serde::de::Error::unknown_variant(
                 &s,
-                &["MarkSweep", "MarkCompactTwoFinger, MarkCompactLisp2"],
+                &[
+                    "MarkSweep",
+                    "MarkCompact_TwoFinger",
+                    "MarkCompact_Lisp2",
+                    "MarkCompact_Jonkers",
+                    "MarkCompact_Compressor",
+                    "Copying",
+                    "Generational",
+                    "ReferenceCounting",
+                    "Immix",
+                    "Treadmill",
+                ],
             )),
         }
     }
@@ -66,11 +113,142 @@ impl<'de> Deserialize<'de> for GCType {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum GCEvent {
-    GCPhase { msg: String },
-    MarkObject { addr: usize, size: usize },
-    FreeObject { addr: usize, size: usize },
-    MoveObject { from: usize, to: usize, size: usize },
-    UpdateFwdPtr { old: usize, new: usize },
+    GCPhase {
+        msg: String,
+    },
+    MarkObject {
+        addr: usize,
+        size: usize,
+        /// Simulated GC thread that marked the object, when marking in parallel.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        worker: Option<usize>,
+    },
+    ShadeObject {
+        addr: usize,
+        size: usize,
+    },
+    FreeObject {
+        addr: usize,
+        size: usize,
+    },
+    MoveObject {
+        from: usize,
+        to: usize,
+        size: usize,
+    },
+    UpdateFwdPtr {
+        old: usize,
+        new: usize,
+    },
+    ThreadPointer {
+        field: usize,
+        obj: usize,
+    },
+    UnthreadPointer {
+        field: usize,
+        new: usize,
+    },
+    CollectGeneration {
+        generation: Generation,
+    },
+    PromoteObject {
+        from: usize,
+        to: usize,
+        size: usize,
+    },
+    IncRef {
+        addr: usize,
+        count: usize,
+    },
+    DecRef {
+        addr: usize,
+        count: usize,
+    },
+    ChangeColor {
+        addr: usize,
+        color: Color,
+    },
+    MarkLine {
+        line: usize,
+    },
+    MoveSegmentPointer {
+        pointer: SegmentPointer,
+        from: usize,
+        to: usize,
+    },
+    ClearWeakRef {
+        field: usize,
+        target: usize,
+    },
+    ResurrectObject {
+        addr: usize,
+        size: usize,
+    },
+    EnqueueFinalizer {
+        addr: usize,
+    },
+    RunFinalizer {
+        addr: usize,
+    },
+    ReclaimFinalized {
+        addr: usize,
+        size: usize,
+    },
+    EphemeronIteration {
+        iteration: usize,
+        ready: usize,
+    },
+    ClearEphemeron {
+        field: usize,
+    },
+    SkipPinned {
+        addr: usize,
+        size: usize,
+    },
+    StealWork {
+        thief: usize,
+        victim: usize,
+        addr: usize,
+    },
+    ScanCard {
+        card: usize,
+        start: usize,
+        size: usize,
+    },
+    NewTlab {
+        thread: usize,
+        start: usize,
+        end: usize,
+    },
+    RetireTlab {
+        thread: usize,
+        start: usize,
+        end: usize,
+        waste: usize,
+    },
+    /// An allocation of `size` words did not fit, the collection that
+    /// follows was triggered by it.
+    AllocationFailure {
+        size: usize,
+    },
+    /// The heap was resized from `from` to `to` words.
+    ResizeHeap {
+        from: usize,
+        to: usize,
+    },
+}
+
+/// Collector metadata kept outside of object headers. It is sent along
+/// with the GC results so that the client can draw it next to the heap.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SideTables {
+    /// One bit per heap word, set for the words of live objects.
+    pub mark_bitmap: Vec<bool>,
+    /// Number of heap words covered by each entry of `offset_table`.
+    pub block_size: usize,
+    /// Where the first live word of each block moves to: the number of
+    /// live words preceding it, unless a pinned object comes first.
+    pub offset_table: Vec<usize>,
 }
 
 impl GCEvent {
@@ -79,15 +257,69 @@ impl GCEvent {
     }
 }
 
-pub fn init_collector(gc_ty: &GCType) -> Box<dyn GarbageCollector> {
-    match gc_ty {
-        GCType::MarkSweep => Box::new(MarkSweep::new()),
-        GCType::MarkCompact(algo) => Box::new(MarkCompact::new(algo.clone())),
+pub fn init_collector(rts_cfg: &ProgramRuntimeConfig) -> Box<dyn GarbageCollector> {
+    let mark_bitmap = match rts_cfg.mark_bits {
+        MarkBits::Header => None,
+        MarkBits::Bitmap => Some(rts_cfg.mark_granularity),
+    };
+    match &rts_cfg.gc_ty {
+        GCType::MarkSweep => match rts_cfg.mark_budget {
+            Some(budget) => Box::new(MarkSweep::incremental(
+                budget,
+                rts_cfg.write_barrier,
+                rts_cfg.large_object_threshold,
+                mark_bitmap,
+                rts_cfg.size_classes.clone(),
+            )),
+            None => Box::new(MarkSweep::new(
+                rts_cfg.large_object_threshold,
+                rts_cfg.gc_workers,
+                mark_bitmap,
+                rts_cfg.size_classes.clone(),
+            )),
+        },
+        GCType::MarkCompact(algo) => Box::new(MarkCompact::new(
+            algo.clone(),
+            rts_cfg.large_object_threshold,
+            rts_cfg.gc_workers,
+            mark_bitmap,
+        )),
+        GCType::Copying => Box::new(Copying::new()),
+        GCType::Generational => Box::new(Generational::new(
+            rts_cfg.nursery_size,
+            rts_cfg.promotion_age,
+            rts_cfg.card_size,
+        )),
+        GCType::ReferenceCounting => Box::new(ReferenceCounting::new()),
+        GCType::Immix => Box::new(Immix::new()),
+        GCType::Treadmill => Box::new(Treadmill::new(
+            rts_cfg.mark_budget,
+            rts_cfg.write_barrier,
+            rts_cfg.cell_size.unwrap_or(treadmill::CELL_SIZE),
+        )),
     }
 }
 
 pub trait GarbageCollector: Send {
-    fn collect(&self, heap: &mut Heap) -> Result<(GCStats, Vec<GCEvent>), VMError>;
+    fn collect(&mut self, heap: &mut Heap) -> Result<(GCStats, Vec<GCEvent>), VMError>;
+    /// Called whenever a fresh heap is created for this collector,
+    /// so that it can reserve or lay out memory before the first allocation.
+    fn init_heap(&mut self, _heap: &mut Heap) {}
+    /// Called after every mutator instruction so that collectors can
+    /// interleave their work with the program. Returns the events of the
+    /// work done.
+    fn step(&mut self, _heap: &mut Heap) -> Vec<GCEvent> {
+        vec![]
+    }
+    /// Side tables built by the last collection, if the collector keeps any.
+    fn side_tables(&self) -> Option<SideTables> {
+        None
+    }
+    /// Whether the heap may be grown or shrunk between collections.
+    /// Collectors that split the heap up front keep it at its initial size.
+    fn resizable_heap(&self) -> bool {
+        true
+    }
     fn ty(&self) -> GCType;
     fn new_instance(&self) -> Box<dyn GarbageCollector>;
 }
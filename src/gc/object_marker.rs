@@ -1,40 +1,214 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
 use crate::{
     heap::Heap,
     object::{Address, Field, ObjAddr},
 };
 
-use super::GCEvent;
+use super::{
+    ephemeron, finalization,
+    stats::{ParallelMarkStats, WorkerStats},
+    GCEvent,
+};
+
+/// Barrier run by `Mutator::write` while an incremental mark is in progress.
+/// It keeps black objects from hiding white ones from the marker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WriteBarrier {
+    /// No barrier: a white object stored into a black one and removed
+    /// from everywhere else is never marked and gets swept while reachable.
+    None,
+    /// Insertion barrier (Dijkstra et al., 1978): shades the stored target.
+    #[default]
+    Dijkstra,
+    /// Deletion barrier (Yuasa, 1990): shades the overwritten target.
+    Yuasa,
+}
+
+/// Where marking records that an object is live.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MarkBits {
+    /// The `marked` flag of each object header.
+    #[default]
+    Header,
+    /// A bitmap owned by the heap, object headers are left untouched.
+    Bitmap,
+}
+
+/// Mark bits kept apart from the objects, one per `granularity` heap words.
+/// An object is marked through the bit of the granule it starts in.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MarkBitmap {
+    pub granularity: usize,
+    pub bits: Vec<bool>,
+}
+
+impl MarkBitmap {
+    pub fn new(heap_size: usize, granularity: usize) -> Self {
+        let granularity = granularity.max(1);
+        MarkBitmap {
+            granularity,
+            bits: vec![false; heap_size.div_ceil(granularity)],
+        }
+    }
+
+    fn bit(&mut self, addr: ObjAddr) -> Option<&mut bool> {
+        self.bits.get_mut(addr / self.granularity)
+    }
+
+    /// Carries the mark of an object moved from `from` to `to`,
+    /// which a header mark does by itself.
+    pub fn move_mark(&mut self, from: ObjAddr, to: ObjAddr) {
+        let marked = self.bit(from).is_some_and(std::mem::take);
+        if let Some(bit) = self.bit(to) {
+            *bit = marked;
+        }
+    }
+
+    /// Follows the heap to a new size, the bits of new words being clear.
+    pub fn resize(&mut self, heap_size: usize) {
+        self.bits
+            .resize(heap_size.div_ceil(self.granularity), false);
+    }
+}
+
+/// Gives a fresh heap a mark bitmap of the given granularity, if any.
+/// The granularity is capped at the heap's alignment, since objects that
+/// shared a bit could not be told apart by marking.
+pub fn reserve_mark_bitmap(heap: &mut Heap, granularity: Option<usize>) {
+    let alignment = heap.alignment.max(1);
+    heap.mark_bitmap = granularity
+        .map(|granularity| MarkBitmap::new(heap.memory.len(), granularity.min(alignment)));
+}
+
+/// Tri-colour state of a mark that is spread across several ticks.
+/// White objects are unmarked, grey ones are marked and waiting on the
+/// mark stack, black ones are marked and have had their fields scanned.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IncrementalMark {
+    pub grey: Vec<ObjAddr>,
+    pub barrier: WriteBarrier,
+}
 
 pub trait ObjectMarker {
     fn mark_from_roots(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
         // Clear all existing marks
         // Current MarkSweep impl needs this
         // TODO: either move it into MarkSweep or just do for MarkSweep only
-        for obj in heap.objects.values_mut() {
-            obj.header.marked = false;
-        }
+        clear_marks(heap);
 
-        // Mark objects starting from roots
-        let roots: Vec<usize> = heap.roots.iter().cloned().collect();
+        // Mark objects starting from roots,
+        // including the ones waiting on the finalization queue
+        let roots: Vec<usize> = heap
+            .roots
+            .iter()
+            .chain(heap.finalization_queue.iter())
+            .cloned()
+            .collect();
         for root in roots {
             self.mark(&root, heap, eventlog);
         }
     }
 
+    /// Marks from the roots with `workers` simulated GC threads, each owning
+    /// a mark deque. The roots are dealt out round-robin. Every round, each
+    /// worker in turn marks the object at the bottom of its own deque and
+    /// pushes the children there; a worker whose deque is empty steals the
+    /// entry at the top of the fullest deque instead. Taking turns in a fixed
+    /// order keeps the outcome deterministic.
+    fn parallel_mark_from_roots(
+        &self,
+        heap: &mut Heap,
+        eventlog: &mut Vec<GCEvent>,
+        workers: usize,
+    ) -> ParallelMarkStats {
+        clear_marks(heap);
+
+        let workers = workers.max(1);
+        let mut deques: Vec<VecDeque<ObjAddr>> = vec![VecDeque::new(); workers];
+        let roots = heap.roots.iter().chain(heap.finalization_queue.iter());
+        for (i, root) in roots.enumerate() {
+            deques[i % workers].push_back(*root);
+        }
+
+        let mut stats = ParallelMarkStats {
+            rounds: 0,
+            workers: vec![WorkerStats::default(); workers],
+        };
+        while deques.iter().any(|deque| !deque.is_empty()) {
+            stats.rounds += 1;
+            for (worker, worker_stats) in stats.workers.iter_mut().enumerate() {
+                if let Some(size) = mark_next(heap, eventlog, &mut deques[worker], worker) {
+                    worker_stats.marked += 1;
+                    worker_stats.marked_words += size;
+                } else if let Some((victim, addr)) = steal(&mut deques, worker) {
+                    eventlog.push(GCEvent::StealWork {
+                        thief: worker,
+                        victim,
+                        addr,
+                    });
+                    worker_stats.steals += 1;
+                } else {
+                    worker_stats.idle_rounds += 1;
+                }
+            }
+        }
+        stats
+    }
+
+    /// Marks the values of ephemerons whose keys are marked, iterating
+    /// until no more values become reachable.
+    fn mark_ephemerons(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        if !ephemeron::any(heap) {
+            return;
+        }
+        for iteration in 1.. {
+            let ready = ephemeron::ready_values(heap, is_marked);
+            eventlog.push(GCEvent::EphemeronIteration {
+                iteration,
+                ready: ready.len(),
+            });
+            if ready.is_empty() {
+                break;
+            }
+            for addr in ready {
+                self.mark(&addr, heap, eventlog);
+            }
+        }
+    }
+
+    /// Resurrects the finalizable objects left unmarked and queues their
+    /// finalizers. Everything they point to is marked as well.
+    fn resurrect_finalizable(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        let unreachable = finalization::unreachable_finalizable(heap, is_marked);
+        if unreachable.is_empty() {
+            return;
+        }
+        for addr in unreachable {
+            finalization::log_resurrection(heap, eventlog, addr);
+            self.mark(&addr, heap, eventlog);
+            finalization::enqueue(heap, eventlog, addr);
+        }
+        // Resurrected objects may be the keys of more ephemerons
+        self.mark_ephemerons(heap, eventlog);
+    }
+
     fn mark(&self, addr: &ObjAddr, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
         let mut stack = Vec::new();
         stack.push(*addr);
 
         while let Some(current_addr) = stack.pop() {
-            if let Some(obj) = heap.objects.get_mut(&current_addr) {
-                if obj.header.marked {
-                    continue; // Already marked, no need to continue
-                }
-
-                obj.header.marked = true;
+            if is_marked(heap, current_addr) {
+                continue; // Already marked, no need to continue
+            }
+            if set_marked(heap, current_addr) {
+                let obj = &heap.objects[&current_addr];
                 eventlog.push(GCEvent::MarkObject {
                     addr: current_addr,
                     size: obj.size(),
+                    worker: None,
                 });
 
                 for field in &obj.fields {
@@ -48,18 +222,201 @@ pub trait ObjectMarker {
             }
         }
     }
+
+    /// Begins an incremental mark: every object turns white and the roots grey.
+    fn start_incremental_mark(
+        &self,
+        heap: &mut Heap,
+        barrier: WriteBarrier,
+        eventlog: &mut Vec<GCEvent>,
+    ) {
+        clear_marks(heap);
+        heap.incremental_mark = Some(IncrementalMark {
+            grey: vec![],
+            barrier,
+        });
+
+        // Pushed in reverse so that roots are scanned in address order
+        let roots: Vec<ObjAddr> = heap
+            .roots
+            .iter()
+            .chain(heap.finalization_queue.iter())
+            .rev()
+            .cloned()
+            .collect();
+        for root in roots {
+            shade(heap, root, eventlog);
+        }
+    }
+
+    /// Blackens at most `budget` grey objects, shading their white children.
+    /// Returns true once no grey objects are left.
+    fn incremental_mark_step(
+        &self,
+        heap: &mut Heap,
+        budget: usize,
+        eventlog: &mut Vec<GCEvent>,
+    ) -> bool {
+        for _ in 0..budget {
+            let Some(addr) = heap.incremental_mark.as_mut().and_then(|m| m.grey.pop()) else {
+                break;
+            };
+            let Some(obj) = heap.objects.get(&addr) else {
+                continue;
+            };
+            eventlog.push(GCEvent::MarkObject {
+                addr,
+                size: obj.size(),
+                worker: None,
+            });
+
+            let children: Vec<ObjAddr> = obj
+                .fields
+                .iter()
+                .filter_map(|field| match field {
+                    Field::Ref {
+                        addr: Address::Ptr(ptr),
+                    } => Some(*ptr),
+                    _ => None,
+                })
+                .collect();
+            for child in children {
+                shade(heap, child, eventlog);
+            }
+        }
+
+        heap.incremental_mark
+            .as_ref()
+            .filter(|mark| !mark.grey.is_empty())
+            .is_none()
+    }
+}
+
+/// Marks the first unmarked object found at the bottom of `deque` on behalf
+/// of `worker` and pushes its children. Returns the size of the object, or
+/// nothing if the deque ran dry.
+fn mark_next(
+    heap: &mut Heap,
+    eventlog: &mut Vec<GCEvent>,
+    deque: &mut VecDeque<ObjAddr>,
+    worker: usize,
+) -> Option<usize> {
+    while let Some(addr) = deque.pop_back() {
+        if is_marked(heap, addr) || !set_marked(heap, addr) {
+            continue;
+        }
+        let obj = &heap.objects[&addr];
+        eventlog.push(GCEvent::MarkObject {
+            addr,
+            size: obj.size(),
+            worker: Some(worker),
+        });
+        for field in &obj.fields {
+            if let Field::Ref {
+                addr: Address::Ptr(child),
+            } = field
+            {
+                deque.push_back(*child);
+            }
+        }
+        return Some(obj.size());
+    }
+    None
+}
+
+/// Moves the entry at the top of the fullest deque, the lowest worker id
+/// winning ties, over to the deque of `thief`. The last entry of a deque
+/// is left to its owner, otherwise idle workers would pass it back and
+/// forth without marking it.
+fn steal(deques: &mut [VecDeque<ObjAddr>], thief: usize) -> Option<(usize, ObjAddr)> {
+    let victim = (0..deques.len())
+        .filter(|victim| *victim != thief && deques[*victim].len() > 1)
+        .max_by_key(|victim| (deques[*victim].len(), std::cmp::Reverse(*victim)))?;
+    let addr = deques[victim].pop_front()?;
+    deques[thief].push_back(addr);
+    Some((victim, addr))
+}
+
+/// Turns a white object grey by marking it and pushing it on the mark stack.
+pub fn shade(heap: &mut Heap, addr: ObjAddr, eventlog: &mut Vec<GCEvent>) {
+    if heap.incremental_mark.is_none() || is_marked(heap, addr) || !set_marked(heap, addr) {
+        return;
+    }
+    if let Some(mark) = heap.incremental_mark.as_mut() {
+        mark.grey.push(addr);
+    }
+    eventlog.push(GCEvent::ShadeObject {
+        addr,
+        size: heap.objects[&addr].size(),
+    });
+}
+
+/// Applies the barrier of the mark in progress to a pointer store
+/// that replaced `old` with `new`.
+pub fn write_barrier(heap: &mut Heap, old: &Address, new: ObjAddr, eventlog: &mut Vec<GCEvent>) {
+    let Some(mark) = heap.incremental_mark.as_ref() else {
+        return;
+    };
+    match (mark.barrier, old) {
+        (WriteBarrier::Dijkstra, _) => shade(heap, new, eventlog),
+        (WriteBarrier::Yuasa, Address::Ptr(old)) => shade(heap, *old, eventlog),
+        _ => {}
+    }
 }
 
+// Mark bits are read and written through the functions below,
+// which use the heap's mark bitmap if it has one, object headers otherwise.
+
 pub fn is_marked(heap: &Heap, addr: ObjAddr) -> bool {
-    if let Some(obj) = heap.objects.get(&addr) {
-        obj.header.marked
-    } else {
-        false
+    let Some(obj) = heap.objects.get(&addr) else {
+        return false;
+    };
+    match &heap.mark_bitmap {
+        Some(bitmap) => bitmap.bits[addr / bitmap.granularity],
+        None => obj.header.marked,
+    }
+}
+
+/// Marks the object at `addr`. Returns false if there is no such object.
+pub fn set_marked(heap: &mut Heap, addr: ObjAddr) -> bool {
+    let Some(obj) = heap.objects.get_mut(&addr) else {
+        return false;
+    };
+    match heap
+        .mark_bitmap
+        .as_mut()
+        .and_then(|bitmap| bitmap.bit(addr))
+    {
+        Some(bit) => *bit = true,
+        None => obj.header.marked = true,
     }
+    heap.write_object_words(addr);
+    true
 }
 
 pub fn unmark(heap: &mut Heap, addr: ObjAddr) {
-    if let Some(obj) = heap.objects.get_mut(&addr) {
-        obj.header.marked = false;
+    let Some(obj) = heap.objects.get_mut(&addr) else {
+        return;
+    };
+    match heap
+        .mark_bitmap
+        .as_mut()
+        .and_then(|bitmap| bitmap.bit(addr))
+    {
+        Some(bit) => *bit = false,
+        None => obj.header.marked = false,
+    }
+    heap.write_object_words(addr);
+}
+
+pub fn clear_marks(heap: &mut Heap) {
+    match heap.mark_bitmap.as_mut() {
+        Some(bitmap) => bitmap.bits.fill(false),
+        None => {
+            for obj in heap.objects.values_mut() {
+                obj.header.marked = false;
+            }
+            heap.sync_words();
+        }
     }
 }
@@ -0,0 +1,262 @@
+use crate::{
+    error::VMError,
+    heap::Heap,
+    object::{Address, Color, Field, ObjAddr},
+};
+
+use super::{common::clear_weak_refs, stats::GCStats, GCEvent, GCType, GarbageCollector};
+
+// Reference counting,
+// introduced by Collins in 1960.
+// Every object header carries the number of references to it: roots count
+// as one, and `Mutator::write` adjusts the counts whenever a pointer field
+// is overwritten. Memory is reclaimed the moment a count drops to zero, so
+// the work is spread across mutator ticks instead of happening in `GC`.
+// Weak references to an object are cleared as it is freed.
+//
+// Counts never drop to zero inside a garbage cycle, so a `GC` instruction
+// runs synchronous cycle collection by trial deletion (Bacon & Rajan, 2001)
+// over the objects buffered as possible cycle roots.
+pub struct ReferenceCounting {}
+
+impl Default for ReferenceCounting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReferenceCounting {
+    pub fn new() -> Self {
+        ReferenceCounting {}
+    }
+
+    /// Trial-deletes the internal references of every subgraph reachable
+    /// from a purple candidate. Candidates that are no longer purple are
+    /// dropped from the buffer.
+    fn mark_roots(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        eventlog.push(GCEvent::phase("mark roots".to_string()));
+        let candidates: Vec<ObjAddr> = heap.candidate_roots.iter().cloned().collect();
+        for addr in candidates {
+            match heap.objects.get(&addr) {
+                Some(obj) if obj.header.color == Color::Purple && obj.header.rc > 0 => {
+                    mark_gray(heap, eventlog, addr);
+                }
+                _ => {
+                    heap.candidate_roots.remove(&addr);
+                }
+            }
+        }
+    }
+
+    /// Objects whose count survived trial deletion are referenced from
+    /// outside the subgraph and get their counts restored; the rest are white.
+    fn scan_roots(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        eventlog.push(GCEvent::phase("scan roots".to_string()));
+        let candidates: Vec<ObjAddr> = heap.candidate_roots.iter().cloned().collect();
+        for addr in candidates {
+            scan(heap, eventlog, addr);
+        }
+    }
+
+    fn collect_roots(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        eventlog.push(GCEvent::phase("collect white".to_string()));
+        let candidates = std::mem::take(&mut heap.candidate_roots);
+        for addr in candidates {
+            collect_white(heap, eventlog, addr);
+        }
+    }
+}
+
+/// Start addresses of the objects `addr` points to.
+fn children(heap: &Heap, addr: ObjAddr) -> Vec<ObjAddr> {
+    heap.objects
+        .get(&addr)
+        .map(|obj| {
+            obj.fields
+                .iter()
+                .filter_map(|field| match field {
+                    Field::Ref {
+                        addr: Address::Ptr(ptr),
+                    } => heap.lookup_object_addr(*ptr).ok(),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn color(heap: &Heap, addr: ObjAddr) -> Color {
+    heap.objects.get(&addr).unwrap().header.color
+}
+
+fn set_color(heap: &mut Heap, eventlog: &mut Vec<GCEvent>, addr: ObjAddr, color: Color) {
+    let obj = heap.objects.get_mut(&addr).unwrap();
+    if obj.header.color != color {
+        obj.header.color = color;
+        eventlog.push(GCEvent::ChangeColor { addr, color });
+    }
+}
+
+fn adjust_count(heap: &mut Heap, eventlog: &mut Vec<GCEvent>, addr: ObjAddr, increment: bool) {
+    let obj = heap.objects.get_mut(&addr).unwrap();
+    if increment {
+        obj.header.rc += 1;
+        eventlog.push(GCEvent::IncRef {
+            addr,
+            count: obj.header.rc,
+        });
+    } else {
+        obj.header.rc = obj.header.rc.saturating_sub(1);
+        eventlog.push(GCEvent::DecRef {
+            addr,
+            count: obj.header.rc,
+        });
+    }
+}
+
+/// Greys the subgraph reachable from `addr`, removing the counts its
+/// internal pointers contribute. Children are only decremented once they
+/// come off the worklist, which keeps the order of the recursive algorithm.
+fn mark_gray(heap: &mut Heap, eventlog: &mut Vec<GCEvent>, addr: ObjAddr) {
+    // (object, whether the pointer to it is to be discounted)
+    let mut worklist = vec![(addr, false)];
+    while let Some((addr, decrement)) = worklist.pop() {
+        if decrement {
+            adjust_count(heap, eventlog, addr, false);
+        }
+        if color(heap, addr) == Color::Gray {
+            continue;
+        }
+        set_color(heap, eventlog, addr, Color::Gray);
+        worklist.extend(children(heap, addr).into_iter().rev().map(|c| (c, true)));
+    }
+}
+
+fn scan(heap: &mut Heap, eventlog: &mut Vec<GCEvent>, addr: ObjAddr) {
+    let mut worklist = vec![addr];
+    while let Some(addr) = worklist.pop() {
+        if color(heap, addr) != Color::Gray {
+            continue;
+        }
+        if heap.objects.get(&addr).unwrap().header.rc > 0 {
+            scan_black(heap, eventlog, addr);
+        } else {
+            set_color(heap, eventlog, addr, Color::White);
+            worklist.extend(children(heap, addr).into_iter().rev());
+        }
+    }
+}
+
+/// Blackens the subgraph reachable from `addr`, restoring the counts
+/// `mark_gray` removed.
+fn scan_black(heap: &mut Heap, eventlog: &mut Vec<GCEvent>, addr: ObjAddr) {
+    // (object, whether the pointer to it is to be counted again)
+    let mut worklist = vec![(addr, false)];
+    while let Some((addr, increment)) = worklist.pop() {
+        if increment {
+            adjust_count(heap, eventlog, addr, true);
+            if color(heap, addr) == Color::Black {
+                continue;
+            }
+        }
+        set_color(heap, eventlog, addr, Color::Black);
+        worklist.extend(children(heap, addr).into_iter().rev().map(|c| (c, true)));
+    }
+}
+
+/// Frees the white subgraph reachable from `addr`, children before
+/// the objects pointing to them.
+fn collect_white(heap: &mut Heap, eventlog: &mut Vec<GCEvent>, addr: ObjAddr) {
+    // (object, whether its children have been collected already)
+    let mut worklist = vec![(addr, false)];
+    while let Some((addr, visited)) = worklist.pop() {
+        if visited {
+            let size = heap.objects.get(&addr).unwrap().size();
+            clear_weak_refs(heap, eventlog, |_, live| live != addr);
+            match heap.free_object(addr) {
+                Ok(_) => eventlog.push(GCEvent::FreeObject { addr, size }),
+                Err(_e) => panic!("collect_white:free_object at {addr:}"),
+            }
+            continue;
+        }
+        if !heap.objects.contains_key(&addr)
+            || color(heap, addr) != Color::White
+            || heap.candidate_roots.contains(&addr)
+        {
+            continue;
+        }
+        set_color(heap, eventlog, addr, Color::Black);
+        worklist.push((addr, true));
+        worklist.extend(children(heap, addr).into_iter().rev().map(|c| (c, false)));
+    }
+}
+
+/// Increments the count of the object `ptr` points into.
+pub fn inc_ref(heap: &mut Heap, ptr: ObjAddr, eventlog: &mut Vec<GCEvent>) {
+    let Ok(addr) = heap.lookup_object_addr(ptr) else {
+        return;
+    };
+    adjust_count(heap, eventlog, addr, true);
+    set_color(heap, eventlog, addr, Color::Black);
+}
+
+/// Decrements the count of the object `ptr` points into. Objects whose count
+/// drops to zero are freed and the counts of their children are decremented
+/// in turn. Objects that stay alive are buffered as possible cycle roots.
+pub fn dec_ref(heap: &mut Heap, ptr: ObjAddr, eventlog: &mut Vec<GCEvent>) {
+    let mut worklist = vec![ptr];
+
+    while let Some(ptr) = worklist.pop() {
+        let Ok(addr) = heap.lookup_object_addr(ptr) else {
+            continue;
+        };
+        adjust_count(heap, eventlog, addr, false);
+
+        let obj = heap.objects.get(&addr).unwrap();
+        if obj.header.rc == 0 {
+            let size = obj.size();
+            for field in obj.fields.iter().rev() {
+                if let Field::Ref {
+                    addr: Address::Ptr(child),
+                } = field
+                {
+                    worklist.push(*child);
+                }
+            }
+            heap.candidate_roots.remove(&addr);
+            clear_weak_refs(heap, eventlog, |_, live| live != addr);
+            match heap.free_object(addr) {
+                Ok(_) => eventlog.push(GCEvent::FreeObject { addr, size }),
+                Err(_e) => panic!("dec_ref:free_object at {addr:}"),
+            }
+        } else if obj.header.color != Color::Purple {
+            set_color(heap, eventlog, addr, Color::Purple);
+            heap.candidate_roots.insert(addr);
+        }
+    }
+}
+
+impl GarbageCollector for ReferenceCounting {
+    fn collect(&mut self, heap: &mut Heap) -> Result<(GCStats, Vec<GCEvent>), VMError> {
+        let mut eventlog = vec![GCEvent::phase("ReferenceCounting: START".to_string())];
+
+        self.mark_roots(heap, &mut eventlog);
+        self.scan_roots(heap, &mut eventlog);
+        self.collect_roots(heap, &mut eventlog);
+
+        eventlog.push(GCEvent::phase("ReferenceCounting: END".to_string()));
+        Ok((GCStats::new(), eventlog))
+    }
+
+    fn init_heap(&mut self, heap: &mut Heap) {
+        heap.ref_counting = true;
+    }
+
+    fn ty(&self) -> GCType {
+        GCType::ReferenceCounting
+    }
+
+    fn new_instance(&self) -> Box<dyn GarbageCollector> {
+        Box::new(ReferenceCounting::new())
+    }
+}
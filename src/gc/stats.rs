@@ -1,11 +1,30 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GCStats {}
+pub struct GCStats {
+    /// Load balance of the GC workers, if marking ran in parallel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_mark: Option<ParallelMarkStats>,
+    /// Cost of the card table, if the collector keeps one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_table: Option<CardTableStats>,
+    /// Thread-local allocation buffers retired since the previous collection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tlab: Option<TlabStats>,
+    /// Heap words taken up by the headers of the objects the collection
+    /// left behind, if headers occupy the heap.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_words: Option<usize>,
+}
 
 impl GCStats {
     pub fn new() -> Self {
-        GCStats {}
+        GCStats {
+            parallel_mark: None,
+            card_table: None,
+            tlab: None,
+            header_words: None,
+        }
     }
 }
 
@@ -14,3 +33,39 @@ impl Default for GCStats {
         Self::new()
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParallelMarkStats {
+    /// Rounds it took to drain every mark deque. Each worker marks at most
+    /// one object per round, so this is the simulated length of the mark.
+    pub rounds: usize,
+    pub workers: Vec<WorkerStats>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkerStats {
+    /// Objects marked by the worker.
+    pub marked: usize,
+    /// Heap words taken up by those objects.
+    pub marked_words: usize,
+    /// Entries the worker took from the deques of other workers.
+    pub steals: usize,
+    /// Rounds the worker found nothing to mark nor steal.
+    pub idle_rounds: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CardTableStats {
+    /// Cards the mutator dirtied since the previous collection.
+    pub dirtied: usize,
+    /// Dirty cards the collection scanned for roots.
+    pub scanned: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TlabStats {
+    /// Buffers retired, either full or at the start of the collection.
+    pub retired: usize,
+    /// Words the retired buffers left unused.
+    pub waste: usize,
+}
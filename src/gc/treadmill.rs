@@ -0,0 +1,309 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::VMError,
+    free_list::FreeList,
+    heap::Heap,
+    object::{Address, Field, ObjAddr},
+};
+
+use super::{
+    common::clear_weak_refs, object_marker::WriteBarrier, stats::GCStats, GCEvent, GCType,
+    GarbageCollector,
+};
+
+/// Number of heap words in a treadmill cell, unless configured otherwise.
+pub const CELL_SIZE: usize = 4;
+
+/// Pointers marking where each segment of the treadmill begins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SegmentPointer {
+    Free,
+    Bottom,
+    Top,
+    Scan,
+}
+
+const POINTERS: [SegmentPointer; 4] = [
+    SegmentPointer::Free,
+    SegmentPointer::Bottom,
+    SegmentPointer::Top,
+    SegmentPointer::Scan,
+];
+
+/// The treadmill: a cyclic doubly-linked list of fixed-size cells, kept
+/// here as four segments in ring order. Each segment begins at one of the
+/// pointers, so an empty segment's pointer coincides with the next one.
+/// - `free` (from `free`): cells available for allocation.
+/// - `from` (from `bottom`): white cells, not reached yet in this cycle.
+/// - `to` (from `top`): grey cells, reached but not scanned. Scanning takes
+///   the cell next to `scan`, shading inserts cells next to `from`.
+/// - `new` (from `scan`): black cells, scanned or allocated in this cycle.
+///
+/// Cells are identified by the heap address they start at. An object takes
+/// its whole cell, and the words of the heap past the last whole cell
+/// belong to no cell at all.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Segments {
+    pub cell_size: usize,
+    pub free: VecDeque<ObjAddr>,
+    pub from: VecDeque<ObjAddr>,
+    pub to: VecDeque<ObjAddr>,
+    pub new: VecDeque<ObjAddr>,
+    pub barrier: WriteBarrier,
+}
+
+impl Segments {
+    pub fn new(heap_size: usize, cell_size: usize, barrier: WriteBarrier) -> Self {
+        Segments {
+            cell_size,
+            free: (0..heap_size / cell_size).map(|c| c * cell_size).collect(),
+            from: VecDeque::new(),
+            to: VecDeque::new(),
+            new: VecDeque::new(),
+            barrier,
+        }
+    }
+
+    /// A collection cycle is in progress as long as there are grey cells.
+    pub fn is_tracing(&self) -> bool {
+        !self.to.is_empty()
+    }
+
+    fn pointers(&self) -> [Option<ObjAddr>; 4] {
+        let segments = [&self.free, &self.from, &self.to, &self.new];
+        std::array::from_fn(|k| (0..4).find_map(|d| segments[(k + d) % 4].front().copied()))
+    }
+}
+
+/// Runs `op` on the treadmill and logs every segment pointer it moved.
+fn moving_pointers<T>(
+    heap: &mut Heap,
+    eventlog: &mut Vec<GCEvent>,
+    op: impl FnOnce(&mut Segments) -> T,
+) -> Option<T> {
+    let segments = heap.treadmill.as_mut()?;
+    let before = segments.pointers();
+    let result = op(segments);
+    let after = segments.pointers();
+
+    for (pointer, (from, to)) in POINTERS.iter().zip(before.iter().zip(after.iter())) {
+        if let (Some(from), Some(to)) = (from, to) {
+            if from != to {
+                eventlog.push(GCEvent::MoveSegmentPointer {
+                    pointer: *pointer,
+                    from: *from,
+                    to: *to,
+                });
+            }
+        }
+    }
+    Some(result)
+}
+
+/// Takes the cell at `free` for an object of `size` words.
+/// The cell joins the black segment.
+pub fn allocate(heap: &mut Heap, size: usize, eventlog: &mut Vec<GCEvent>) -> Option<ObjAddr> {
+    if size > heap.treadmill.as_ref()?.cell_size {
+        return None;
+    }
+    moving_pointers(heap, eventlog, |segments| {
+        let cell = segments.free.pop_front()?;
+        segments.new.push_back(cell);
+        Some(cell)
+    })?
+}
+
+/// Moves the white cell `ptr` points into over to the grey segment.
+fn shade(heap: &mut Heap, ptr: ObjAddr, eventlog: &mut Vec<GCEvent>) {
+    let Ok(addr) = heap.lookup_object_addr(ptr) else {
+        return;
+    };
+    let size = heap.objects.get(&addr).unwrap().size();
+    let shaded = moving_pointers(heap, eventlog, |segments| {
+        let pos = segments.from.iter().position(|cell| *cell == addr)?;
+        segments.from.remove(pos);
+        segments.to.push_front(addr);
+        Some(())
+    });
+    if let Some(Some(())) = shaded {
+        eventlog.push(GCEvent::ShadeObject { addr, size });
+    }
+}
+
+/// Blackens the grey cell next to `scan` and shades its children.
+/// Returns false if there was nothing left to scan.
+fn scan(heap: &mut Heap, eventlog: &mut Vec<GCEvent>) -> bool {
+    let Some(Some(addr)) = moving_pointers(heap, eventlog, |segments| {
+        let cell = segments.to.pop_back()?;
+        segments.new.push_front(cell);
+        Some(cell)
+    }) else {
+        return false;
+    };
+
+    let obj = heap.objects.get(&addr).unwrap();
+    eventlog.push(GCEvent::MarkObject {
+        addr,
+        size: obj.size(),
+        worker: None,
+    });
+    let children: Vec<ObjAddr> = obj
+        .fields
+        .iter()
+        .filter_map(|field| match field {
+            Field::Ref {
+                addr: Address::Ptr(ptr),
+            } => Some(*ptr),
+            _ => None,
+        })
+        .collect();
+    for child in children {
+        shade(heap, child, eventlog);
+    }
+    true
+}
+
+/// Write barrier, invoked on every pointer store while a cycle is in progress.
+pub fn write_barrier(heap: &mut Heap, old: &Address, new: ObjAddr, eventlog: &mut Vec<GCEvent>) {
+    let Some(segments) = heap.treadmill.as_ref() else {
+        return;
+    };
+    if !segments.is_tracing() {
+        return;
+    }
+    match (segments.barrier, old) {
+        (WriteBarrier::Dijkstra, _) => shade(heap, new, eventlog),
+        (WriteBarrier::Yuasa, Address::Ptr(old)) => shade(heap, *old, eventlog),
+        _ => {}
+    }
+}
+
+// Treadmill,
+// introduced by Baker in 1992.
+// Objects never move: colour is given by the segment of the treadmill a
+// cell sits on, and changing colour means relinking the cell. A `GC` flips
+// the treadmill, i.e. swaps pointers so that the white cells left over from
+// the previous cycle become free and the black ones become white, then
+// shades the roots. Scanning is spread across the following mutator
+// instructions when `mark_budget` is set; once no grey cells are left,
+// the white cells are garbage and wait for the next flip.
+pub struct Treadmill {
+    mark_budget: Option<usize>,
+    barrier: WriteBarrier,
+    cell_size: usize,
+}
+
+impl Treadmill {
+    pub fn new(mark_budget: Option<usize>, barrier: WriteBarrier, cell_size: usize) -> Self {
+        Treadmill {
+            mark_budget,
+            barrier,
+            cell_size,
+        }
+    }
+
+    fn flip(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        eventlog.push(GCEvent::phase("flip".to_string()));
+        let garbage = moving_pointers(heap, eventlog, |segments| {
+            let garbage: Vec<ObjAddr> = segments.from.drain(..).collect();
+            segments.free.extend(garbage.iter().cloned());
+            segments.from = std::mem::take(&mut segments.new);
+            garbage
+        })
+        .unwrap_or_default();
+
+        // Weak references must not reach whatever takes over a garbage cell
+        clear_weak_refs(heap, eventlog, |_, addr| !garbage.contains(&addr));
+
+        // The treadmill reuses garbage cells as they are,
+        // the heap just has to stop listing their objects
+        for addr in garbage {
+            if let Some(obj) = heap.objects.get(&addr) {
+                let size = obj.size();
+                match heap.free_object(addr) {
+                    Ok(_) => eventlog.push(GCEvent::FreeObject { addr, size }),
+                    Err(_e) => panic!("flip:free_object at {addr:}"),
+                }
+                // The part of the cell the object did not fill is free again too
+                heap.free_list.insert(addr, self.cell_size);
+            }
+        }
+    }
+
+    fn shade_roots(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>) {
+        eventlog.push(GCEvent::phase("shade roots".to_string()));
+        let roots: Vec<ObjAddr> = heap.roots.iter().cloned().collect();
+        for root in roots {
+            shade(heap, root, eventlog);
+        }
+    }
+
+    fn trace(&self, heap: &mut Heap, eventlog: &mut Vec<GCEvent>, budget: usize) {
+        for _ in 0..budget {
+            if !scan(heap, eventlog) {
+                break;
+            }
+        }
+    }
+}
+
+impl GarbageCollector for Treadmill {
+    fn collect(&mut self, heap: &mut Heap) -> Result<(GCStats, Vec<GCEvent>), VMError> {
+        let mut eventlog = vec![GCEvent::phase("Treadmill: START".to_string())];
+
+        if heap.treadmill.as_ref().is_some_and(Segments::is_tracing) {
+            // A `GC` during a cycle finishes it right away
+            eventlog.push(GCEvent::phase("finish scanning".to_string()));
+            self.trace(heap, &mut eventlog, usize::MAX);
+        } else {
+            self.flip(heap, &mut eventlog);
+            self.shade_roots(heap, &mut eventlog);
+            if self.mark_budget.is_none() {
+                eventlog.push(GCEvent::phase("scan".to_string()));
+                self.trace(heap, &mut eventlog, usize::MAX);
+            }
+        }
+
+        eventlog.push(GCEvent::phase("Treadmill: END".to_string()));
+        Ok((GCStats::new(), eventlog))
+    }
+
+    fn step(&mut self, heap: &mut Heap) -> Vec<GCEvent> {
+        let Some(budget) = self.mark_budget else {
+            return vec![];
+        };
+        if !heap.treadmill.as_ref().is_some_and(Segments::is_tracing) {
+            return vec![];
+        }
+        let mut eventlog = vec![GCEvent::phase("scan".to_string())];
+        self.trace(heap, &mut eventlog, budget);
+        eventlog
+    }
+
+    fn init_heap(&mut self, heap: &mut Heap) {
+        let heap_size = heap.memory.len();
+        let segments = Segments::new(heap_size, self.cell_size, self.barrier);
+        // Only whole cells can be allocated into
+        heap.free_list = FreeList::new(vec![]);
+        if !segments.free.is_empty() {
+            heap.free_list
+                .insert(0, segments.free.len() * self.cell_size);
+        }
+        heap.treadmill = Some(segments);
+    }
+
+    fn ty(&self) -> GCType {
+        GCType::Treadmill
+    }
+
+    fn new_instance(&self) -> Box<dyn GarbageCollector> {
+        Box::new(Treadmill::new(
+            self.mark_budget,
+            self.barrier,
+            self.cell_size,
+        ))
+    }
+}
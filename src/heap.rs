@@ -1,11 +1,23 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    buddy::BuddyBlocks,
     error::VMError,
     free_list::FreeList,
-    object::{ObjAddr, Object},
+    gc::{
+        card_table::CardTable,
+        generational::Generations,
+        immix::Regions,
+        large_object_space::LargeObjectSpace,
+        object_marker::{is_marked, IncrementalMark, MarkBitmap},
+        treadmill::Segments,
+    },
+    object::{Field, ObjAddr, Object},
+    segregated_fits::SizeClasses,
+    tlab::Tlabs,
+    word::{self, Word},
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -15,21 +27,71 @@ pub struct Heap {
     pub free_list: FreeList,
     pub memory: Vec<MemoryCell>,
     pub alignment: usize,
+    /// Young/old split of the heap, present for generational collection only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generations: Option<Generations>,
+    /// Whether object headers carry reference counts maintained by the mutator.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ref_counting: bool,
+    /// Possible roots of garbage cycles, buffered on decrement.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub candidate_roots: BTreeSet<ObjAddr>,
+    /// Mark stack of an incremental mark spanning several ticks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incremental_mark: Option<IncrementalMark>,
+    /// Blocks and lines of the heap, present for mark-region collection only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regions: Option<Regions>,
+    /// Cells of the heap linked into segments, present for the treadmill only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub treadmill: Option<Segments>,
+    /// Resurrected objects whose finalizers have yet to run.
+    #[serde(default, skip_serializing_if = "VecDeque::is_empty")]
+    pub finalization_queue: VecDeque<ObjAddr>,
+    /// Pages at the end of the heap holding large objects, if configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub large_objects: Option<LargeObjectSpace>,
+    /// Mark bits of collectors that keep them out of object headers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mark_bitmap: Option<MarkBitmap>,
+    /// Cards dirtied by pointer stores, if the collector keeps a card table.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_table: Option<CardTable>,
+    /// Roving pointer of next-fit allocation, where the previous one ended.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_fit: Option<ObjAddr>,
+    /// Free lists of small size classes, if allocation uses segregated fits.
+    /// `free_list` is then the general pool the classes are refilled from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_classes: Option<SizeClasses>,
+    /// Thread-local allocation buffers, if allocation bumps through them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tlabs: Option<Tlabs>,
+    /// Power-of-two blocks of buddy allocation, which takes over the free list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buddy: Option<BuddyBlocks>,
+    /// Whether `memory` holds the encoded words of the objects, see `word`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub word_memory: bool,
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct MemoryCell {
     pub status: CellStatus,
+    /// Raw tagged word stored in the cell, in the word memory model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub word: Option<u64>,
 }
 
 impl MemoryCell {
     pub fn new(status: CellStatus) -> Self {
-        MemoryCell { status }
+        MemoryCell { status, word: None }
     }
 
     pub fn free() -> Self {
         MemoryCell {
             status: CellStatus::Free,
+            word: None,
         }
     }
 }
@@ -39,7 +101,11 @@ pub enum CellStatus {
     Free,
     Allocated,
     Marked,
+    Grey,
+    Pinned,
     Used,
+    /// Header words in front of the fields of an object.
+    Header,
 }
 
 impl Heap {
@@ -50,6 +116,21 @@ impl Heap {
             memory: vec![MemoryCell::free(); size],
             free_list: free_list![(0, size)],
             alignment,
+            generations: None,
+            ref_counting: false,
+            candidate_roots: BTreeSet::new(),
+            incremental_mark: None,
+            regions: None,
+            treadmill: None,
+            finalization_queue: VecDeque::new(),
+            large_objects: None,
+            mark_bitmap: None,
+            card_table: None,
+            next_fit: None,
+            size_classes: None,
+            tlabs: None,
+            buddy: None,
+            word_memory: false,
         }
     }
 
@@ -60,7 +141,7 @@ impl Heap {
     ///
     /// # Returns
     /// - `Ok(ObjAddr)`: If an object is found that encompasses the given `address`,
-    ///                  the function returns the address at which the object starts in memory.
+    ///   the function returns the address at which the object starts in memory.
     /// - `Err(VMError::SegmentationFault)`: If no such object is found, the function returns a segmentation fault error.
     pub fn lookup_object_addr(&self, address: ObjAddr) -> Result<ObjAddr, VMError> {
         // Find the first object that has an address less than or equal to the given address.
@@ -73,8 +154,209 @@ impl Heap {
         Err(VMError::SegmentationFault)
     }
 
+    /// Returns the field stored at `address`, if it lies within an object.
+    pub fn field_at(&self, address: ObjAddr) -> Option<&Field> {
+        let obj_addr = self.lookup_object_addr(address).ok()?;
+        self.objects.get(&obj_addr)?.field(address - obj_addr)
+    }
+
     pub fn calc_free_memory(&self) -> usize {
-        self.free_list.iter().map(|(_, size)| size).sum()
+        let old_free: usize = self.generations.as_ref().map_or(0, |gens| {
+            gens.old_free_list.iter().map(|(_, size)| size).sum()
+        });
+        let class_free = self
+            .size_classes
+            .as_ref()
+            .map_or(0, |classes| classes.free_words());
+        let buddy_free = self.buddy.as_ref().map_or(0, |buddy| buddy.free_words());
+        self.free_list.iter().map(|(_, size)| size).sum::<usize>()
+            + old_free
+            + class_free
+            + buddy_free
+    }
+
+    /// Whether the heap can be resized in place. Layouts that are laid out
+    /// for the size the heap started with, e.g. generations or a large
+    /// object space at its end, keep it fixed.
+    pub fn resizable(&self) -> bool {
+        self.generations.is_none()
+            && self.regions.is_none()
+            && self.treadmill.is_none()
+            && self.large_objects.is_none()
+            && self.buddy.is_none()
+    }
+
+    /// Extends the heap to `size` words, the new words being free.
+    pub fn grow(&mut self, size: usize) {
+        let old_size = self.memory.len();
+        if size <= old_size {
+            return;
+        }
+        let cell = MemoryCell {
+            word: self.word_memory.then_some(0),
+            ..MemoryCell::free()
+        };
+        self.memory.resize(size, cell);
+        self.free_list.insert(old_size, size - old_size);
+        if let Some(bitmap) = self.mark_bitmap.as_mut() {
+            bitmap.resize(size);
+        }
+    }
+
+    /// Trims the heap down to `size` words, or as far as its free tail
+    /// goes, and returns the size it ends up with.
+    pub fn shrink(&mut self, size: usize) -> usize {
+        let old_size = self.memory.len();
+        let Some((tail_start, tail_size)) = self.free_list.to_vec().last().cloned() else {
+            return old_size;
+        };
+        if tail_start + tail_size != old_size || size >= old_size {
+            return old_size;
+        }
+        let new_size = size.max(tail_start);
+        self.free_list.remove(tail_start);
+        if new_size > tail_start {
+            self.free_list.insert(tail_start, new_size - tail_start);
+        }
+        self.memory.truncate(new_size);
+        if let Some(bitmap) = self.mark_bitmap.as_mut() {
+            bitmap.resize(new_size);
+        }
+        if self.next_fit.is_some_and(|rover| rover >= new_size) {
+            self.next_fit = None;
+        }
+        new_size
+    }
+
+    /// Switches to the word memory model, every cell starting out blank.
+    pub fn use_words(&mut self) {
+        self.word_memory = true;
+        for cell in &mut self.memory {
+            cell.word = Some(0);
+        }
+    }
+
+    /// Decodes the word stored at `addr`, in the word memory model.
+    pub fn word_at(&self, addr: ObjAddr) -> Option<Word> {
+        self.memory.get(addr)?.word.map(word::decode)
+    }
+
+    /// Field `index` of the object at `addr`. In the word memory model it is
+    /// decoded from its cell, of which the object table is only a mirror.
+    pub fn load_field(&self, addr: ObjAddr, index: usize) -> Option<Field> {
+        let object = self.objects.get(&addr)?;
+        let field = object.fields.get(index)?;
+        if !self.word_memory {
+            return Some(field.clone());
+        }
+        match self.word_at(object.field_addr(addr, index))? {
+            Word::Field(field) => Some(field),
+            _ => None,
+        }
+    }
+
+    /// Stores `field` as field `index` of the object at `addr`. In the word
+    /// memory model it is encoded into its cell and the object table picks
+    /// up what the cell decodes to.
+    pub fn store_field(
+        &mut self,
+        addr: ObjAddr,
+        index: usize,
+        field: Field,
+    ) -> Result<(), VMError> {
+        let object = self
+            .objects
+            .get_mut(&addr)
+            .ok_or(VMError::SegmentationFault)?;
+        let cell = object.field_addr(addr, index);
+        let slot = object
+            .fields
+            .get_mut(index)
+            .ok_or(VMError::SegmentationFault)?;
+        if !self.word_memory {
+            *slot = field;
+            return Ok(());
+        }
+        word::check_field(&field)?;
+        let raw = word::encode(&Word::Field(field));
+        self.memory[cell].word = Some(raw);
+        if let Word::Field(field) = word::decode(raw) {
+            *slot = field;
+        }
+        Ok(())
+    }
+
+    /// Where the object at `addr` has been copied to. In the word memory
+    /// model this is the forwarding pointer written over its header word.
+    pub fn forwarded_to(&self, addr: ObjAddr) -> Option<ObjAddr> {
+        if !self.word_memory {
+            return self.objects.get(&addr)?.header.fwd_addr;
+        }
+        match self.word_at(addr)? {
+            Word::Forward { to } => Some(to),
+            _ => None,
+        }
+    }
+
+    /// Raw words of `[start, start + size)`.
+    pub fn words(&self, start: ObjAddr, size: usize) -> Vec<Option<u64>> {
+        self.memory[start..start + size]
+            .iter()
+            .map(|cell| cell.word)
+            .collect()
+    }
+
+    /// Stores raw `words` from `start` on, in the word memory model.
+    pub fn store_words(&mut self, start: ObjAddr, words: &[Option<u64>]) {
+        if !self.word_memory {
+            return;
+        }
+        for (cell, word) in self.memory.iter_mut().skip(start).zip(words) {
+            cell.word = *word;
+        }
+    }
+
+    /// Stores the words of the object at `addr` into its cells.
+    pub fn write_object_words(&mut self, addr: ObjAddr) {
+        if !self.word_memory {
+            return;
+        }
+        let Some(object) = self.objects.get(&addr) else {
+            return;
+        };
+        for (cell, raw) in self
+            .memory
+            .iter_mut()
+            .skip(addr)
+            .zip(word::encode_object(object))
+        {
+            cell.word = Some(raw);
+        }
+    }
+
+    /// Blanks the words of `[start, start + size)`.
+    pub fn clear_words(&mut self, start: ObjAddr, size: usize) {
+        if !self.word_memory {
+            return;
+        }
+        for cell in self.memory.iter_mut().skip(start).take(size) {
+            cell.word = Some(0);
+        }
+    }
+
+    /// Stores the words of every object, picking up the fields collectors
+    /// updated in the object table. Cells of objects that were dropped
+    /// without being freed keep their stale words, like real memory would.
+    pub fn sync_words(&mut self) {
+        let addrs: Vec<ObjAddr> = self.objects.keys().cloned().collect();
+        for addr in addrs {
+            self.write_object_words(addr);
+        }
+    }
+
+    /// Heap words taken up by object headers.
+    pub fn header_overhead(&self) -> usize {
+        self.objects.values().map(|obj| obj.header.words).sum()
     }
 
     pub fn merge_free_ranges(&mut self) {
@@ -85,11 +367,26 @@ impl Heap {
         if let Some(object) = self.objects.remove(&addr) {
             let size = object.size();
 
-            // Add the deallocated space back to free_list
-            self.free_list.insert(addr, size);
+            match self.large_objects.as_mut() {
+                // Large objects give their pages back instead
+                Some(los) if los.contains(addr) => los.release(addr),
+                // Objects of a size class give their cell back to the class
+                _ if self
+                    .size_classes
+                    .as_mut()
+                    .is_some_and(|classes| classes.release(addr, size)) => {}
+                // Buddy blocks are coalesced with their buddies instead
+                _ if self.buddy.as_mut().is_some_and(|buddy| buddy.release(addr)) => {}
+                _ => {
+                    // Add the deallocated space back to free_list
+                    self.free_list.insert(addr, size);
+
+                    // Use unified merge function
+                    self.free_list.merge_adjacent_blocks();
+                }
+            }
 
-            // Use unified merge function
-            self.free_list.merge_adjacent_blocks();
+            self.clear_words(addr, size);
 
             // Remove the deallocated object address from the roots set, if present
             self.roots.remove(&addr);
@@ -115,16 +412,27 @@ impl Heap {
 
         // Insert the saved object at the new 'to' address
         self.objects.insert(to, object.clone());
+        if let Some(bitmap) = self.mark_bitmap.as_mut() {
+            bitmap.move_mark(from, to);
+        }
 
         // If the object was a root at its old address, then update the roots set
         if is_root {
             self.roots.insert(to);
         }
 
+        // Likewise for the finalization queue
+        for queued in self.finalization_queue.iter_mut() {
+            if *queued == from {
+                *queued = to;
+            }
+        }
+
         // Update the free_list for the 'to' address by removing the 'to' block
         // and adding a block that accounts for the object's size
         let object_size = object.size();
         self.objects.insert(to, object);
+        self.write_object_words(to);
         self.free_list.insert(from, object_size);
         // update free_list to account for new block
         self.free_list.merge_adjacent_blocks();
@@ -138,12 +446,24 @@ impl Heap {
             cell.status = CellStatus::Free;
         }
 
-        // Set the memory cells occupied by objects to Allocated
+        // Set the memory cells occupied by objects to Allocated or Pinned,
+        // or to their colour while an incremental mark is in progress
         for (addr, object) in &self.objects {
+            let status = match (&self.incremental_mark, &self.treadmill) {
+                (Some(mark), _) if mark.grey.contains(addr) => CellStatus::Grey,
+                (Some(_), _) if is_marked(self, *addr) => CellStatus::Marked,
+                (_, Some(segments)) if segments.to.contains(addr) => CellStatus::Grey,
+                _ if object.header.pinned => CellStatus::Pinned,
+                _ => CellStatus::Allocated,
+            };
             let size = object.size();
             for offset in 0..size {
                 if let Some(cell) = self.memory.get_mut(*addr + offset) {
-                    cell.status = CellStatus::Allocated;
+                    cell.status = if offset < object.header.words {
+                        CellStatus::Header
+                    } else {
+                        status
+                    };
                 }
             }
         }
@@ -188,9 +508,8 @@ impl Heap {
 
 #[cfg(test)]
 mod tests {
-    use crate::object::Field;
-
     use super::*;
+    use crate::object::Address;
 
     #[test]
     fn test_new_heap() {
@@ -315,4 +634,59 @@ mod tests {
             assert_eq!(heap.aligned_position(i), i);
         }
     }
+
+    #[test]
+    fn test_grow_and_shrink() {
+        let mut heap = Heap::new(8, 0);
+        heap.objects
+            .insert(0, Object::new(vec![Field::new_scalar(1); 6]));
+        heap.free_list = free_list![(6, 2)];
+
+        // The new words join the free tail
+        heap.grow(16);
+        assert_eq!(heap.memory.len(), 16);
+        assert_eq!(heap.free_list.to_vec(), vec![(6, 10)]);
+
+        // Only the free tail can be trimmed
+        assert_eq!(heap.shrink(4), 6);
+        assert_eq!(heap.memory.len(), 6);
+        assert!(heap.free_list.to_vec().is_empty());
+        assert_eq!(heap.shrink(4), 6);
+    }
+
+    #[test]
+    fn test_object_words() {
+        let mut heap = Heap::new(8, 0);
+        heap.use_words();
+        let mut object = Object::new(vec![Field::new_scalar(7), Field::new_ref(Address::Null)]);
+        object.header.words = 1;
+        heap.objects.insert(2, object);
+        heap.write_object_words(2);
+
+        assert!(matches!(
+            heap.word_at(2),
+            Some(Word::Header {
+                size: 3,
+                marked: false
+            })
+        ));
+        assert!(matches!(
+            heap.word_at(3),
+            Some(Word::Field(Field::Scalar { value: 7 }))
+        ));
+        assert!(matches!(
+            heap.word_at(4),
+            Some(Word::Field(Field::Ref {
+                addr: Address::Null
+            }))
+        ));
+
+        // A forwarding pointer is written over the header
+        heap.objects.get_mut(&2).unwrap().header.fwd_addr = Some(5);
+        heap.write_object_words(2);
+        assert!(matches!(heap.word_at(2), Some(Word::Forward { to: 5 })));
+
+        heap.free_object(2).unwrap();
+        assert!(matches!(heap.word_at(2), Some(Word::Free)));
+    }
 }
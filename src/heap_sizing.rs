@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{gc::GCEvent, heap::Heap};
+
+/// Collections in a row that have to leave the heap below its occupancy
+/// threshold before it is shrunk.
+const SHRINK_AFTER: usize = 2;
+
+/// How the heap is resized while a program runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "policy")]
+pub enum HeapSizing {
+    /// The heap keeps the size it started with.
+    #[default]
+    Fixed,
+    /// The heap grows by `factor` whenever an allocation does not fit,
+    /// up to `max_size` words.
+    Grow { factor: f64, max_size: usize },
+    /// The heap grows like it does for `Grow`, and is shrunk by `factor`,
+    /// down to `min_size` words, once collections keep leaving less than
+    /// `occupancy` of it in use.
+    Elastic {
+        factor: f64,
+        max_size: usize,
+        min_size: usize,
+        occupancy: f64,
+    },
+}
+
+/// Grows the heap so that at least `size` more words are free, unless
+/// the policy or the heap layout does not allow it.
+pub fn grow(heap: &mut Heap, sizing: HeapSizing, size: usize) -> Option<GCEvent> {
+    let (HeapSizing::Grow { factor, max_size }
+    | HeapSizing::Elastic {
+        factor, max_size, ..
+    }) = sizing
+    else {
+        return None;
+    };
+    let from = heap.memory.len();
+    let to = ((from as f64 * factor).ceil() as usize)
+        .max(from + size)
+        .min(max_size);
+    if to <= from || !heap.resizable() {
+        return None;
+    }
+    heap.grow(to);
+    Some(GCEvent::ResizeHeap { from, to })
+}
+
+/// Shrinks the heap after a collection if the last `SHRINK_AFTER` ones
+/// left it below its occupancy threshold. `low_occupancy` counts those.
+pub fn shrink(heap: &mut Heap, sizing: HeapSizing, low_occupancy: &mut usize) -> Option<GCEvent> {
+    let HeapSizing::Elastic {
+        factor,
+        min_size,
+        occupancy,
+        ..
+    } = sizing
+    else {
+        return None;
+    };
+    let from = heap.memory.len();
+    let used = from - heap.calc_free_memory();
+    if (used as f64) < occupancy * from as f64 {
+        *low_occupancy += 1;
+    } else {
+        *low_occupancy = 0;
+    }
+    if *low_occupancy < SHRINK_AFTER || !heap.resizable() {
+        return None;
+    }
+    let to = heap.shrink(((from as f64 / factor).ceil() as usize).max(min_size));
+    if to == from {
+        return None;
+    }
+    *low_occupancy = 0;
+    Some(GCEvent::ResizeHeap { from, to })
+}
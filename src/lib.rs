@@ -1,13 +1,28 @@
 /// Application.
 pub mod session;
 
-///
+/// Free memory bookkeeping.
 #[macro_use]
 pub mod free_list;
 
+/// Segregated size-class free lists.
+pub mod segregated_fits;
+
+/// Binary buddy allocation.
+pub mod buddy;
+
+/// Thread-local allocation buffers.
+pub mod tlab;
+
 /// UI memory representation.
 pub mod heap;
 
+/// Heap growth and shrink policies.
+pub mod heap_sizing;
+
+/// Tagged heap words.
+pub mod word;
+
 /// Mutator implementation.
 pub mod mutator;
 
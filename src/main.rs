@@ -9,8 +9,8 @@ use tokio_tungstenite::{
 };
 use tungstenite::Message;
 
+use gcviz::file_utils::CustomError;
 use gcviz::{file_utils, wsmsg::WSMessageResponse};
-use gcviz::{file_utils::CustomError, gc::init_collector};
 use gcviz::{
     program::Program,
     wsmsg::{WSMessageRequest, WSMessageRequestType},
@@ -129,21 +129,16 @@ fn load_program(session: &mut Session, file_name: Option<String>) -> Result<(),
     let (program, rts_cfg): (Program, ProgramRuntimeConfig) = if let Some(fname) = file_name {
         // Load program using provided file name.
         info!("Loading program from provided file name: {}", fname);
-        file_utils::load_program(&fname)
+        file_utils::load_program(&fname)?
     } else if let Ok(env_file) = env::var("PROGRAM_FILE") {
         // Load program from environment variable.
         info!("Loading program from environment variable: {}", env_file);
-        file_utils::load_program(&env_file)
+        file_utils::load_program(&env_file)?
     } else {
         // Generate a new program.
         info!("Generating program using simulation params");
         session.gen_program()
     };
-    session.program = program;
-    session.vm.reset_heap(rts_cfg.heap_size);
-    session.rts_cfg = rts_cfg;
-    session.vm.heap.alignment = session.rts_cfg.alignment;
-    session.vm.collector = init_collector(&session.rts_cfg.gc_ty);
-    assert!(session.rts_cfg.alignment == session.vm.heap.alignment);
+    session.load(program, rts_cfg);
     Ok(())
 }
@@ -1,6 +1,12 @@
 use crate::error::VMError;
+use crate::gc::{
+    object_marker::write_barrier,
+    reference_counting::{dec_ref, inc_ref},
+    treadmill, GCEvent,
+};
 use crate::heap::Heap;
 use crate::object::{Address, Field, ObjAddr, Value};
+use crate::word::Word;
 
 pub struct Mutator;
 
@@ -16,25 +22,41 @@ impl Mutator {
     }
 
     pub fn read(&self, heap: &Heap, address: ObjAddr) -> Result<Value, VMError> {
-        self.read_rec(heap, address)
+        self.read_rec(heap, address, 0)
     }
 
-    fn read_rec(&self, heap: &Heap, address: ObjAddr) -> Result<Value, VMError> {
-        let object_addr = heap.lookup_object_addr(address)?;
-        let object = heap
-            .objects
-            .get(&object_addr)
-            .ok_or(VMError::SegmentationFault)?;
+    fn read_rec(&self, heap: &Heap, address: ObjAddr, hops: usize) -> Result<Value, VMError> {
+        // A chain of pointers longer than the number of objects must loop
+        if hops > heap.objects.len() {
+            return Err(VMError::CyclicReference(format!(
+                "Pointer chain at 0x{address:X} never reaches a scalar",
+            )));
+        }
 
-        let field_index = address - object_addr;
-        let field = object
-            .fields
-            .get(field_index)
-            .ok_or(VMError::SegmentationFault)?;
+        let field = if heap.word_memory {
+            // Decode the word in the cell, headers and free cells hold no field
+            match heap.word_at(address) {
+                Some(Word::Field(field)) => field,
+                _ => return Err(VMError::SegmentationFault),
+            }
+        } else {
+            let object_addr = heap.lookup_object_addr(address)?;
+            let object = heap
+                .objects
+                .get(&object_addr)
+                .ok_or(VMError::SegmentationFault)?;
+            object
+                .field(address - object_addr)
+                .ok_or(VMError::SegmentationFault)?
+                .clone()
+        };
 
-        match field {
-            Field::Ref { addr } => match addr {
-                Address::Ptr(a) => self.read(heap, *a),
+        match &field {
+            Field::Ref { addr }
+            | Field::WeakRef { addr }
+            | Field::EphemeronKey { addr }
+            | Field::EphemeronValue { addr } => match addr {
+                Address::Ptr(a) => self.read_rec(heap, *a, hops + 1),
                 Address::Null => Err(VMError::NullPointerException(format!(
                     "Attempted to dereference NULL address at 0x{address:X}",
                 ))),
@@ -43,28 +65,73 @@ impl Mutator {
         }
     }
 
-    pub fn write(&self, heap: &mut Heap, address: ObjAddr, value: Value) -> Result<(), VMError> {
+    /// Pins or unpins the object `address` points into.
+    /// Returns the start address of the object.
+    pub fn set_pinned(
+        &self,
+        heap: &mut Heap,
+        address: ObjAddr,
+        pinned: bool,
+    ) -> Result<ObjAddr, VMError> {
         let object_addr = heap.lookup_object_addr(address)?;
         let object = heap
             .objects
             .get_mut(&object_addr)
             .ok_or(VMError::SegmentationFault)?;
+        object.header.pinned = pinned;
+        Ok(object_addr)
+    }
 
-        let field_index = address - object_addr;
-        let field = object
-            .fields
-            .get_mut(field_index)
+    /// Writes `value` into the field at `address`. Returns the events of any
+    /// collector work the write triggered, e.g. reference count updates.
+    /// In the word memory model the value is encoded into the field's cell,
+    /// and scalars that do not fit in a word are rejected.
+    pub fn write(
+        &self,
+        heap: &mut Heap,
+        address: ObjAddr,
+        value: Value,
+    ) -> Result<Vec<GCEvent>, VMError> {
+        let object_addr = heap.lookup_object_addr(address)?;
+        let index = heap
+            .objects
+            .get(&object_addr)
+            .and_then(|object| (address - object_addr).checked_sub(object.header.words))
+            .ok_or(VMError::SegmentationFault)?;
+        let old = heap
+            .load_field(object_addr, index)
             .ok_or(VMError::SegmentationFault)?;
 
-        match field {
-            Field::Ref { addr } => {
-                *addr = Address::Ptr(value);
-                Ok(())
+        let new = match &old {
+            Field::Ref { .. } => Field::new_ref(Address::Ptr(value)),
+            // Weak pointers are invisible to barriers and reference counts,
+            // ephemerons are traced once the rest of the heap is marked
+            Field::WeakRef { .. } => Field::new_weak_ref(Address::Ptr(value)),
+            Field::EphemeronKey { .. } => Field::new_ephemeron_key(Address::Ptr(value)),
+            Field::EphemeronValue { .. } => Field::new_ephemeron_value(Address::Ptr(value)),
+            Field::Scalar { .. } => Field::new_scalar(value),
+        };
+        heap.store_field(object_addr, index, new)?;
+
+        let mut eventlog = vec![];
+        if let Field::Ref { addr: old } = old {
+            // Card marking barrier, or the generational write barrier
+            // remembering old-to-young pointers precisely
+            if let Some(cards) = heap.card_table.as_mut() {
+                cards.dirty(address);
+            } else if let Some(gens) = heap.generations.as_mut() {
+                gens.record_write(address, value);
             }
-            Field::Scalar { value: val } => {
-                *val = value;
-                Ok(())
+            // Tri-colour barrier of an incremental mark in progress
+            write_barrier(heap, &old, value, &mut eventlog);
+            treadmill::write_barrier(heap, &old, value, &mut eventlog);
+            if heap.ref_counting {
+                inc_ref(heap, value, &mut eventlog);
+                if let Address::Ptr(old) = old {
+                    dec_ref(heap, old, &mut eventlog);
+                }
             }
         }
+        Ok(eventlog)
     }
 }
@@ -14,16 +14,29 @@ pub struct Object {
 }
 
 impl Object {
+    /// Heap words the object occupies, its header included.
     pub fn size(&self) -> usize {
-        self.fields.len()
+        self.header.words + self.fields.len()
+    }
+
+    /// The field `offset` words past the start of the object, if the
+    /// offset does not fall into the header.
+    pub fn field(&self, offset: usize) -> Option<&Field> {
+        self.fields.get(offset.checked_sub(self.header.words)?)
+    }
+
+    pub fn field_mut(&mut self, offset: usize) -> Option<&mut Field> {
+        self.fields.get_mut(offset.checked_sub(self.header.words)?)
+    }
+
+    /// Address of field `index` of the object at `addr`.
+    pub fn field_addr(&self, addr: ObjAddr, index: usize) -> ObjAddr {
+        addr + self.header.words + index
     }
 
     pub fn new(fields: Vec<Field>) -> Self {
         Self {
-            header: ObjHeader {
-                marked: false,
-                fwd_addr: None,
-            },
+            header: ObjHeader::default(),
             fields,
         }
     }
@@ -51,10 +64,7 @@ impl Object {
 
         // Create an object with generated fields
         Object {
-            header: ObjHeader {
-                marked: false,
-                fwd_addr: None,
-            },
+            header: ObjHeader::default(),
             fields,
         }
     }
@@ -75,10 +85,52 @@ impl fmt::Display for Object {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct ObjHeader {
     pub marked: bool,
     pub fwd_addr: Option<usize>,
+    /// Number of minor collections survived (generational GC only).
+    pub age: u8,
+    /// Number of references to this object (reference counting only).
+    pub rc: usize,
+    /// Colour used by cycle collection (reference counting only).
+    pub color: Color,
+    /// Last field threaded onto this object (Jonkers compaction only).
+    pub thread: Option<ObjAddr>,
+    /// Progress of the object's finalizer, if it has one.
+    pub finalization: Finalization,
+    /// Pinned objects are never moved by compaction.
+    pub pinned: bool,
+    /// Heap words the header takes up in front of the fields.
+    pub words: usize,
+}
+
+/// Lifecycle of a finalizable object.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Finalization {
+    /// The object has no finalizer, or it is not finalizable anymore.
+    #[default]
+    None,
+    /// The finalizer runs once the object is found unreachable.
+    Pending,
+    /// The object was resurrected and waits on the finalization queue.
+    Queued,
+    /// The finalizer ran, the object is reclaimed once unreachable.
+    Done,
+}
+
+/// Object colours of synchronous cycle collection (Bacon & Rajan, 2001).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Color {
+    /// In use or free.
+    #[default]
+    Black,
+    /// Possible member of a garbage cycle.
+    Gray,
+    /// Member of a garbage cycle.
+    White,
+    /// Possible root of a garbage cycle.
+    Purple,
 }
 
 impl Serialize for ObjHeader {
@@ -92,6 +144,34 @@ impl Serialize for ObjHeader {
             map.serialize_entry("fwd_addr", &fwd_addr)?;
         }
 
+        if self.age > 0 {
+            map.serialize_entry("age", &self.age)?;
+        }
+
+        if self.rc > 0 {
+            map.serialize_entry("rc", &self.rc)?;
+        }
+
+        if self.color != Color::Black {
+            map.serialize_entry("color", &self.color)?;
+        }
+
+        if let Some(thread) = self.thread {
+            map.serialize_entry("thread", &thread)?;
+        }
+
+        if self.finalization != Finalization::None {
+            map.serialize_entry("finalization", &self.finalization)?;
+        }
+
+        if self.pinned {
+            map.serialize_entry("pinned", &self.pinned)?;
+        }
+
+        if self.words > 0 {
+            map.serialize_entry("words", &self.words)?;
+        }
+
         map.end()
     }
 }
@@ -101,29 +181,73 @@ impl<'de> Deserialize<'de> for ObjHeader {
     where
         D: Deserializer<'de>,
     {
-        let mut marked = false;
-        let mut fwd_addr = None;
+        let mut header = ObjHeader::default();
 
         let v: Option<HashMap<String, serde_json::Value>> = Option::deserialize(deserializer)?;
 
         if let Some(map) = v {
             if let Some(m) = map.get("marked") {
-                marked = m.as_bool().unwrap_or(false);
+                header.marked = m.as_bool().unwrap_or(false);
             }
 
             if let Some(f) = map.get("fwd_addr") {
-                fwd_addr = f.as_u64().map(|val| val as usize);
+                header.fwd_addr = f.as_u64().map(|val| val as usize);
+            }
+
+            if let Some(a) = map.get("age") {
+                header.age = a.as_u64().unwrap_or(0) as u8;
+            }
+
+            if let Some(rc) = map.get("rc") {
+                header.rc = rc.as_u64().unwrap_or(0) as usize;
+            }
+
+            if let Some(c) = map.get("color") {
+                header.color = serde_json::from_value(c.clone()).unwrap_or_default();
+            }
+
+            if let Some(t) = map.get("thread") {
+                header.thread = t.as_u64().map(|val| val as usize);
+            }
+
+            if let Some(f) = map.get("finalization") {
+                header.finalization = serde_json::from_value(f.clone()).unwrap_or_default();
+            }
+
+            if let Some(p) = map.get("pinned") {
+                header.pinned = p.as_bool().unwrap_or(false);
+            }
+
+            if let Some(w) = map.get("words") {
+                header.words = w.as_u64().unwrap_or(0) as usize;
             }
         }
 
-        Ok(ObjHeader { marked, fwd_addr })
+        Ok(header)
     }
 }
 
 #[derive(Clone, Debug)]
 pub enum Field {
-    Ref { addr: Address },
-    Scalar { value: Value },
+    Ref {
+        addr: Address,
+    },
+    /// A pointer that does not keep its target alive. Collectors reset it
+    /// to `Address::Null` once the target is reclaimed.
+    WeakRef {
+        addr: Address,
+    },
+    /// Key of an ephemeron, paired with the `EphemeronValue` that follows it.
+    /// The key is weak, the value is traced only while the key is reachable.
+    EphemeronKey {
+        addr: Address,
+    },
+    EphemeronValue {
+        addr: Address,
+    },
+    Scalar {
+        value: Value,
+    },
 }
 
 impl Field {
@@ -134,6 +258,18 @@ impl Field {
     pub fn new_ref(addr: Address) -> Self {
         Field::Ref { addr }
     }
+
+    pub fn new_weak_ref(addr: Address) -> Self {
+        Field::WeakRef { addr }
+    }
+
+    pub fn new_ephemeron_key(addr: Address) -> Self {
+        Field::EphemeronKey { addr }
+    }
+
+    pub fn new_ephemeron_value(addr: Address) -> Self {
+        Field::EphemeronValue { addr }
+    }
 }
 
 impl Serialize for Field {
@@ -152,6 +288,21 @@ impl Serialize for Field {
                 map.serialize_entry("addr", addr)?;
                 map.end()
             }
+            Field::WeakRef { addr } => {
+                let mut map = serde::ser::Serializer::serialize_map(serializer, Some(1))?;
+                map.serialize_entry("weak", addr)?;
+                map.end()
+            }
+            Field::EphemeronKey { addr } => {
+                let mut map = serde::ser::Serializer::serialize_map(serializer, Some(1))?;
+                map.serialize_entry("ephemeron_key", addr)?;
+                map.end()
+            }
+            Field::EphemeronValue { addr } => {
+                let mut map = serde::ser::Serializer::serialize_map(serializer, Some(1))?;
+                map.serialize_entry("ephemeron_value", addr)?;
+                map.end()
+            }
         }
     }
 }
@@ -165,12 +316,22 @@ impl<'de> Deserialize<'de> for Field {
         #[serde(untagged)]
         enum FieldHelper {
             Ref { addr: Address },
+            WeakRef { weak: Address },
+            EphemeronKey { ephemeron_key: Address },
+            EphemeronValue { ephemeron_value: Address },
             Scalar { value: Value },
         }
 
         match FieldHelper::deserialize(deserializer)? {
             FieldHelper::Scalar { value } => Ok(Field::Scalar { value }),
             FieldHelper::Ref { addr } => Ok(Field::Ref { addr }),
+            FieldHelper::WeakRef { weak } => Ok(Field::WeakRef { addr: weak }),
+            FieldHelper::EphemeronKey { ephemeron_key } => Ok(Field::EphemeronKey {
+                addr: ephemeron_key,
+            }),
+            FieldHelper::EphemeronValue { ephemeron_value } => Ok(Field::EphemeronValue {
+                addr: ephemeron_value,
+            }),
         }
     }
 }
@@ -179,6 +340,9 @@ impl fmt::Display for Field {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Field::Ref { addr } => write!(f, "({addr})"),
+            Field::WeakRef { addr } => write!(f, "weak({addr})"),
+            Field::EphemeronKey { addr } => write!(f, "key({addr})"),
+            Field::EphemeronValue { addr } => write!(f, "value({addr})"),
             Field::Scalar { value } => write!(f, "{value}"),
         }
     }
@@ -6,7 +6,7 @@ use serde::{
 };
 
 use crate::{
-    gc::{stats::GCStats, GCEvent},
+    gc::{immix::Regions, object_marker::MarkBitmap, stats::GCStats, GCEvent, SideTables},
     object::{Object, Value},
 };
 
@@ -15,10 +15,29 @@ pub type Program = VecDeque<Instruction>;
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "_type")]
 pub enum Instruction {
-    Allocate { object: Object, is_root: bool },
-    Read { addr: usize },
-    Write { addr: usize, value: Value },
+    Allocate {
+        object: Object,
+        is_root: bool,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        finalizable: bool,
+        /// Simulated mutator thread doing the allocation.
+        thread: usize,
+    },
+    Read {
+        addr: usize,
+    },
+    Write {
+        addr: usize,
+        value: Value,
+    },
     GC,
+    RunFinalizers,
+    Pin {
+        addr: usize,
+    },
+    Unpin {
+        addr: usize,
+    },
 }
 
 impl<'de> Deserialize<'de> for Instruction {
@@ -44,6 +63,8 @@ impl<'de> Deserialize<'de> for Instruction {
                 let mut addr: Option<usize> = None;
                 let mut value: Option<Value> = None;
                 let mut is_root: Option<bool> = None;
+                let mut finalizable: Option<bool> = None;
+                let mut thread: Option<usize> = None;
 
                 while let Some(key) = map.next_key::<String>()? {
                     match key.as_str() {
@@ -52,6 +73,8 @@ impl<'de> Deserialize<'de> for Instruction {
                         "addr" => addr = map.next_value()?,
                         "value" => value = map.next_value()?,
                         "is_root" => is_root = map.next_value()?,
+                        "finalizable" => finalizable = map.next_value()?,
+                        "thread" => thread = map.next_value()?,
                         _ => {}
                     }
                 }
@@ -60,6 +83,8 @@ impl<'de> Deserialize<'de> for Instruction {
                     Some("Allocate") => Ok(Instruction::Allocate {
                         object: object.ok_or_else(|| de::Error::missing_field("object"))?,
                         is_root: is_root.unwrap_or(true),
+                        finalizable: finalizable.unwrap_or(false),
+                        thread: thread.unwrap_or(0),
                     }),
                     Some("Read") => Ok(Instruction::Read {
                         addr: addr.ok_or_else(|| de::Error::missing_field("addr"))?,
@@ -69,12 +94,27 @@ impl<'de> Deserialize<'de> for Instruction {
                         value: value.ok_or_else(|| de::Error::missing_field("value"))?,
                     }),
                     Some("GC") => Ok(Instruction::GC),
+                    Some("RunFinalizers") => Ok(Instruction::RunFinalizers),
+                    Some("Pin") => Ok(Instruction::Pin {
+                        addr: addr.ok_or_else(|| de::Error::missing_field("addr"))?,
+                    }),
+                    Some("Unpin") => Ok(Instruction::Unpin {
+                        addr: addr.ok_or_else(|| de::Error::missing_field("addr"))?,
+                    }),
                     _ => Err(de::Error::custom("Invalid instruction type")),
                 }
             }
         }
 
-        const FIELDS: &[&str] = &["_type", "object", "addr", "value", "is_root"];
+        const FIELDS: &[&str] = &[
+            "_type",
+            "object",
+            "addr",
+            "value",
+            "is_root",
+            "finalizable",
+            "thread",
+        ];
         deserializer.deserialize_struct("Instruction", FIELDS, InstructionVisitor)
     }
 }
@@ -85,17 +125,51 @@ pub enum InstrResult {
     Allocate {
         addr: usize,
         object: Object,
+        gc_eventlog: Vec<GCEvent>,
+        /// Stats of the collection the allocation triggered, if it did not fit.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gc_stats: Option<GCStats>,
     },
     Read {
         addr: usize,
         value: Value,
+        gc_eventlog: Vec<GCEvent>,
     },
     Write {
         addr: usize,
         value: Value,
+        gc_eventlog: Vec<GCEvent>,
     },
     GC {
         stats: GCStats,
         gc_eventlog: Vec<GCEvent>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        side_tables: Option<SideTables>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        regions: Option<Regions>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mark_bitmap: Option<MarkBitmap>,
+    },
+    RunFinalizers {
+        gc_eventlog: Vec<GCEvent>,
     },
+    Pin {
+        addr: usize,
+        pinned: bool,
+        gc_eventlog: Vec<GCEvent>,
+    },
+}
+
+impl InstrResult {
+    /// Events of the collector work done during this instruction.
+    pub fn gc_eventlog_mut(&mut self) -> &mut Vec<GCEvent> {
+        match self {
+            InstrResult::Allocate { gc_eventlog, .. }
+            | InstrResult::Read { gc_eventlog, .. }
+            | InstrResult::Write { gc_eventlog, .. }
+            | InstrResult::GC { gc_eventlog, .. }
+            | InstrResult::RunFinalizers { gc_eventlog }
+            | InstrResult::Pin { gc_eventlog, .. } => gc_eventlog,
+        }
+    }
 }
@@ -1,9 +1,20 @@
 use serde::{Deserialize, Serialize};
 
-use crate::gc::GCType;
+use crate::{
+    allocator::AllocPolicy,
+    gc::{
+        mark_compact::CompactAlgorithm,
+        object_marker::{MarkBits, WriteBarrier},
+        GCType,
+    },
+    heap_sizing::HeapSizing,
+    word::MemoryModel,
+};
 
 static ALIGNMENT: usize = 4;
 static HEAP_SIZE: usize = 512;
+static PROMOTION_AGE: u8 = 2;
+static MARK_GRANULARITY: usize = 1;
 
 /// Program simulation parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +22,89 @@ pub struct ProgramRuntimeConfig {
     pub heap_size: usize,
     pub alignment: usize,
     pub gc_ty: GCType,
+    /// Placement policy of free-list allocation.
+    #[serde(default)]
+    pub alloc_policy: AllocPolicy,
+    /// Words of the thread-local allocation buffers. When set, each
+    /// simulated thread bump-allocates through a buffer carved out of
+    /// the free list, and gets a new one once it is full.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tlab_size: Option<usize>,
+    /// Run the collector when an allocation does not fit and retry it,
+    /// failing with out of memory only if the retry fails as well.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub gc_on_alloc_failure: bool,
+    /// Whether the heap grows when allocations do not fit and shrinks when
+    /// it is mostly free. Only heaps without a size-dependent layout, e.g.
+    /// generations or a large object space, are resized.
+    #[serde(default)]
+    pub heap_sizing: HeapSizing,
+    /// Heap words of the header every allocation reserves in front of the
+    /// object's fields. Headers take no heap space when zero.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub header_words: usize,
+    /// Whether heap cells hold the tagged words of the objects in them,
+    /// which the mutator and Copying then read and write through. Other
+    /// collectors work on the object table and store it back into the cells.
+    #[serde(default)]
+    pub memory_model: MemoryModel,
+    /// Size of the young generation. Defaults to a quarter of the heap.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nursery_size: Option<usize>,
+    /// Heap words per card of a card table. When set, Generational dirties
+    /// cards on pointer stores and scans the dirty old cards at a minor
+    /// collection instead of keeping a precise remembered set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_size: Option<usize>,
+    /// Number of minor collections an object has to survive to be promoted.
+    #[serde(default = "default_promotion_age")]
+    pub promotion_age: u8,
+    /// Objects MarkSweep blackens per mutator instruction. When set, marking
+    /// is incremental: a `GC` starts the mark and the sweep follows once
+    /// no grey objects are left.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mark_budget: Option<usize>,
+    /// Barrier keeping the tri-colour invariant during incremental marking.
+    #[serde(default)]
+    pub write_barrier: WriteBarrier,
+    /// Objects of at least this many words are allocated in a large object
+    /// space at the end of the heap, which compaction never moves.
+    /// Only used by MarkSweep and MarkCompact.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub large_object_threshold: Option<usize>,
+    /// Cell sizes of segregated-fits allocation. Objects that fit one of
+    /// these classes get a cell of the smallest such class, larger ones are
+    /// allocated from the general free list. Only used by MarkSweep.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_classes: Option<Vec<usize>>,
+    /// Words of a treadmill cell, which bounds the size of the objects
+    /// Treadmill can allocate. Defaults to 4.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cell_size: Option<usize>,
+    /// Number of simulated GC threads marking in parallel.
+    /// Only used by MarkSweep and MarkCompact other than Compressor,
+    /// marking is sequential when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gc_workers: Option<usize>,
+    /// Where MarkSweep and MarkCompact keep their mark bits. Compressor
+    /// always marks into a bitmap of its own.
+    #[serde(default)]
+    pub mark_bits: MarkBits,
+    /// Heap words covered by each bit of the mark bitmap, at most the alignment.
+    #[serde(default = "default_mark_granularity")]
+    pub mark_granularity: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+fn default_promotion_age() -> u8 {
+    PROMOTION_AGE
+}
+
+fn default_mark_granularity() -> usize {
+    MARK_GRANULARITY
 }
 
 impl Default for ProgramRuntimeConfig {
@@ -19,6 +113,23 @@ impl Default for ProgramRuntimeConfig {
             heap_size: HEAP_SIZE,
             alignment: ALIGNMENT,
             gc_ty: GCType::MarkSweep,
+            alloc_policy: AllocPolicy::default(),
+            tlab_size: None,
+            gc_on_alloc_failure: false,
+            header_words: 0,
+            memory_model: MemoryModel::default(),
+            heap_sizing: HeapSizing::default(),
+            nursery_size: None,
+            card_size: None,
+            promotion_age: PROMOTION_AGE,
+            mark_budget: None,
+            write_barrier: WriteBarrier::default(),
+            large_object_threshold: None,
+            size_classes: None,
+            cell_size: None,
+            gc_workers: None,
+            mark_bits: MarkBits::default(),
+            mark_granularity: MARK_GRANULARITY,
         }
     }
 }
@@ -29,6 +140,94 @@ impl ProgramRuntimeConfig {
             heap_size,
             alignment,
             gc_ty,
+            ..Default::default()
+        }
+    }
+
+    /// Header words every allocation reserves. Objects in the word memory
+    /// model need at least one to hold their header word.
+    pub fn object_header_words(&self) -> usize {
+        match self.memory_model {
+            MemoryModel::Status => self.header_words,
+            MemoryModel::Words => self.header_words.max(1),
+        }
+    }
+
+    /// Rejects settings the collector cannot honour, rather than ignoring
+    /// them or failing halfway through the program.
+    pub fn validate(&self) -> Result<(), String> {
+        // Semispaces are of the same size and both start aligned
+        if matches!(self.gc_ty, GCType::Copying)
+            && self.heap_size & (2 * self.alignment.max(1) - 1) != 0
+        {
+            return Err(format!(
+                "Copying needs a heap that splits into two aligned semispaces, \
+                 got heap_size {} with alignment {}",
+                self.heap_size, self.alignment
+            ));
+        }
+        // Buddy blocks are only given back when their objects are freed in place
+        if self.alloc_policy == AllocPolicy::Buddy
+            && !matches!(self.gc_ty, GCType::MarkSweep | GCType::ReferenceCounting)
+        {
+            return Err(format!(
+                "Buddy allocation needs a collector that frees objects in place, got {:?}",
+                self.gc_ty
+            ));
+        }
+        // Objects are allocated at the start of a cell
+        if let Some(cell_size) = self.cell_size {
+            if cell_size == 0 || cell_size & (self.alignment.max(1) - 1) != 0 {
+                return Err(format!(
+                    "cell_size has to be a non-zero multiple of the alignment, \
+                     got cell_size {} with alignment {}",
+                    cell_size, self.alignment
+                ));
+            }
+        }
+        // Compressor marks into a bitmap of its own, sequentially
+        if matches!(
+            self.gc_ty,
+            GCType::MarkCompact(CompactAlgorithm::Compressor)
+        ) {
+            if self.gc_workers.is_some() {
+                return Err(
+                    "Compressor marks sequentially, gc_workers is not supported".to_string()
+                );
+            }
+            if self.mark_bits == MarkBits::Bitmap {
+                return Err(
+                    "Compressor keeps its own mark bitmap, mark_bits: Bitmap is not supported"
+                        .to_string(),
+                );
+            }
+        }
+        if let Some(budget) = self.mark_budget {
+            if budget == 0 {
+                return Err("mark_budget has to blacken at least one object".to_string());
+            }
+            if !matches!(self.gc_ty, GCType::MarkSweep | GCType::Treadmill) {
+                return Err(format!(
+                    "mark_budget is only supported by MarkSweep and Treadmill, got {:?}",
+                    self.gc_ty
+                ));
+            }
+            // Incremental marking is done by the mutator thread alone
+            if self.gc_workers.is_some() {
+                return Err("mark_budget and gc_workers cannot be combined".to_string());
+            }
+        }
+        // The retry after a failed allocation needs garbage freed right away,
+        // incremental marks and the treadmill only free it cycles later
+        if self.gc_on_alloc_failure
+            && (self.mark_budget.is_some() || matches!(self.gc_ty, GCType::Treadmill))
+        {
+            return Err(format!(
+                "gc_on_alloc_failure needs a collection that frees garbage at once, \
+                 got {:?} with mark_budget {:?}",
+                self.gc_ty, self.mark_budget
+            ));
         }
+        Ok(())
     }
 }
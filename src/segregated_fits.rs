@@ -0,0 +1,120 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{heap::Heap, object::ObjAddr};
+
+/// Number of cells an exhausted size class carves out of the general pool at once.
+const REFILL_CELLS: usize = 4;
+
+/// Free cells of one size class. Every cell is `size` words long and holds
+/// a single object of at most that size.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SizeClass {
+    pub size: usize,
+    /// Start addresses of the free cells, the next one to hand out last.
+    pub free: Vec<ObjAddr>,
+    /// Start addresses of the cells handed out, the only ones taken back.
+    pub allocated: BTreeSet<ObjAddr>,
+}
+
+/// Segregated fits: one free list per size class on top of the general
+/// pool kept by `Heap::free_list`. Requests that fit a class are served
+/// from its free list, larger ones from the pool. Cells are never
+/// coalesced, a freed cell goes back to its class.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SizeClasses {
+    /// Size classes in increasing order of size.
+    pub classes: Vec<SizeClass>,
+}
+
+impl SizeClasses {
+    pub fn new(sizes: &[usize]) -> Self {
+        let mut sizes: Vec<usize> = sizes.iter().filter(|size| **size > 0).cloned().collect();
+        sizes.sort_unstable();
+        sizes.dedup();
+        SizeClasses {
+            classes: sizes
+                .into_iter()
+                .map(|size| SizeClass {
+                    size,
+                    free: vec![],
+                    allocated: BTreeSet::new(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Index of the smallest class that fits an object of `size` words.
+    pub fn class_of(&self, size: usize) -> Option<usize> {
+        self.classes.iter().position(|class| size <= class.size)
+    }
+
+    /// Gives the cell of a freed object back to its class. Returns false
+    /// if the object was not allocated from a class, e.g. it was bumped
+    /// into a TLAB or is larger than every class.
+    pub fn release(&mut self, addr: ObjAddr, size: usize) -> bool {
+        let Some(class) = self.class_of(size).map(|class| &mut self.classes[class]) else {
+            return false;
+        };
+        if !class.allocated.remove(&addr) {
+            return false;
+        }
+        class.free.push(addr);
+        true
+    }
+
+    /// Words held by free cells of every class.
+    pub fn free_words(&self) -> usize {
+        self.classes
+            .iter()
+            .map(|class| class.size * class.free.len())
+            .sum()
+    }
+}
+
+/// Takes a cell of class `class`, refilling the class from the general
+/// pool if it ran out of cells.
+pub fn allocate(heap: &mut Heap, class: usize) -> Option<ObjAddr> {
+    let classes = heap.size_classes.as_ref()?;
+    if classes.classes[class].free.is_empty() {
+        refill(heap, class)?;
+    }
+    let class = &mut heap.size_classes.as_mut()?.classes[class];
+    let addr = class.free.pop()?;
+    class.allocated.insert(addr);
+    Some(addr)
+}
+
+/// Splits up to `REFILL_CELLS` cells of class `class` off the first block
+/// of the general pool that holds at least one.
+fn refill(heap: &mut Heap, class: usize) -> Option<()> {
+    let size = heap.size_classes.as_ref()?.classes[class].size;
+    let stride = heap.aligned_position(size);
+    let (block_start, block_size, cells_start, cells) = heap
+        .free_list
+        .to_vec()
+        .into_iter()
+        .find_map(|(block_start, block_size)| {
+            let cells_start = heap.aligned_position(block_start);
+            let available = (block_start + block_size).checked_sub(cells_start)?;
+            let cells = (available / stride).min(REFILL_CELLS);
+            (cells > 0).then_some((block_start, block_size, cells_start, cells))
+        })?;
+
+    let cells_end = cells_start + cells * stride;
+    heap.free_list.remove(block_start);
+    if cells_start > block_start {
+        heap.free_list
+            .insert(block_start, cells_start - block_start);
+    }
+    if block_start + block_size > cells_end {
+        heap.free_list
+            .insert(cells_end, block_start + block_size - cells_end);
+    }
+
+    // Lowest address on top, so that cells are handed out in address order
+    let free = &mut heap.size_classes.as_mut()?.classes[class].free;
+    free.extend((0..cells).rev().map(|cell| cells_start + cell * stride));
+    Some(())
+}
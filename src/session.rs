@@ -1,15 +1,16 @@
 use std::{collections::VecDeque, error};
 
 use crate::{
+    allocator::Allocator,
     error::VMError,
-    gc::init_collector,
+    gc::{init_collector, GarbageCollector},
     heap::{CellStatus, MemoryCell},
     log::{Log, LogSource, LOG_CAPACITY},
     program::{InstrResult, Program},
     rts_cfg::ProgramRuntimeConfig,
     simulator::Simulator,
     vm::VirtualMachine,
-    wsmsg::InfoBlockData,
+    wsmsg::{BuddyBlockInfo, BuddyInfo, InfoBlockData, LargeObjectSpaceInfo, SizeClassInfo},
 };
 
 /// Application result type.
@@ -39,11 +40,7 @@ impl Default for Session {
 
 impl Session {
     pub fn new(rts_cfg: ProgramRuntimeConfig) -> Self {
-        let vm = VirtualMachine::new(
-            rts_cfg.alignment,
-            rts_cfg.heap_size,
-            init_collector(&rts_cfg.gc_ty),
-        );
+        let vm = Self::build_vm(&rts_cfg, init_collector(&rts_cfg));
         Self {
             program: VecDeque::new(),
             logs: VecDeque::with_capacity(LOG_CAPACITY),
@@ -55,6 +52,35 @@ impl Session {
         }
     }
 
+    /// A fresh VM running `collector`, with every setting of `rts_cfg` applied.
+    fn build_vm(
+        rts_cfg: &ProgramRuntimeConfig,
+        collector: Box<dyn GarbageCollector>,
+    ) -> VirtualMachine {
+        VirtualMachine::new(
+            rts_cfg.alignment,
+            rts_cfg.heap_size,
+            collector,
+            Allocator::new(
+                rts_cfg.alloc_policy,
+                rts_cfg.tlab_size,
+                rts_cfg.object_header_words(),
+            ),
+            rts_cfg.gc_on_alloc_failure,
+            rts_cfg.heap_sizing,
+            rts_cfg.memory_model,
+        )
+    }
+
+    /// Replaces the program and its configuration, and starts a VM built
+    /// from that configuration as if the session had been created with it.
+    pub fn load(&mut self, program: Program, rts_cfg: ProgramRuntimeConfig) {
+        self.vm = Self::build_vm(&rts_cfg, init_collector(&rts_cfg));
+        self.program = program;
+        self.rts_cfg = rts_cfg;
+        self.instr_ptr = 0;
+    }
+
     fn enqueue_log(&mut self, log: Log) {
         if self.logs.len() == self.log_capacity {
             self.logs.pop_front();
@@ -73,20 +99,53 @@ impl Session {
         if let Some(instruction) = self.program.get(self.instr_ptr) {
             match self.vm.tick(instruction) {
                 Ok(instr_result) => {
+                    // Collector work interleaved with the mutator may have
+                    // freed or recoloured objects
+                    if let InstrResult::Allocate { gc_eventlog, .. }
+                    | InstrResult::Read { gc_eventlog, .. }
+                    | InstrResult::Write { gc_eventlog, .. }
+                    | InstrResult::RunFinalizers { gc_eventlog } = &instr_result
+                    {
+                        if !gc_eventlog.is_empty() {
+                            self.vm.heap.redraw_memory();
+                        }
+                    }
                     match &instr_result {
-                        InstrResult::Allocate { object, addr, .. } => {
+                        InstrResult::Allocate {
+                            object,
+                            addr,
+                            gc_stats,
+                            ..
+                        } => {
+                            if let Some(stats) = gc_stats {
+                                self.enqueue_log(Log::new(
+                                    format!(
+                                        "Allocation failure, collect garbage. Stats: {stats:?}"
+                                    ),
+                                    LogSource::GC,
+                                    Some(self.instr_ptr),
+                                ));
+                            }
                             self.enqueue_log(Log::new(
                                 format!("{object} at 0x{addr:X}"),
                                 LogSource::ALLOC,
                                 Some(self.instr_ptr),
                             ));
+                            let header_words = self
+                                .vm
+                                .heap
+                                .objects
+                                .get(addr)
+                                .map_or(0, |obj| obj.header.words);
                             Self::visualize_allocation(
                                 &mut self.vm.heap.memory,
                                 *addr,
+                                header_words,
                                 object.size(),
+                                object.header.pinned,
                             );
                         }
-                        InstrResult::Read { addr, value } => {
+                        InstrResult::Read { addr, value, .. } => {
                             self.enqueue_log(Log::new(
                                 format!("Read value from 0x{addr:X}. Value: {value}"),
                                 LogSource::MUT,
@@ -94,7 +153,7 @@ impl Session {
                             ));
                             Self::visualize_mutator(&mut self.vm.heap.memory, *addr);
                         }
-                        InstrResult::Write { addr, value } => {
+                        InstrResult::Write { addr, value, .. } => {
                             self.enqueue_log(Log::new(
                                 format!("Write value {value:} to 0x{addr:X}"),
                                 LogSource::MUT,
@@ -102,6 +161,22 @@ impl Session {
                             ));
                             Self::visualize_mutator(&mut self.vm.heap.memory, *addr);
                         }
+                        InstrResult::Pin { addr, pinned, .. } => {
+                            let action = if *pinned { "Pin" } else { "Unpin" };
+                            self.enqueue_log(Log::new(
+                                format!("{action} object at 0x{addr:X}"),
+                                LogSource::MUT,
+                                Some(self.instr_ptr),
+                            ));
+                            self.vm.heap.redraw_memory();
+                        }
+                        InstrResult::RunFinalizers { .. } => {
+                            self.enqueue_log(Log::new(
+                                "Run finalizers".to_string(),
+                                LogSource::MUT,
+                                Some(self.instr_ptr),
+                            ));
+                        }
                         InstrResult::GC { stats, .. } => {
                             self.enqueue_log(Log::new(
                                 format!("Collect garbage. Stats: {stats:?}"),
@@ -137,34 +212,96 @@ impl Session {
             LogSource::VM,
             Some(self.instr_ptr),
         ));
-        self.vm.heap.memory = vec![MemoryCell::new(CellStatus::Free); self.vm.heap.memory.len()];
+        // The heap starts over at its configured size, however it was resized
+        self.vm.heap.memory = vec![MemoryCell::new(CellStatus::Free); self.rts_cfg.heap_size];
 
         // Reinitialize the VM
         let new_collector = self.vm.collector.new_instance();
-        self.vm = VirtualMachine::new(
-            self.vm.heap.alignment,
-            self.vm.heap.memory.len(),
-            new_collector,
-        );
+        self.vm = Self::build_vm(&self.rts_cfg, new_collector);
     }
 
     fn visualize_mutator(memory: &mut [MemoryCell], addr: usize) {
-        memory[addr] = MemoryCell::new(CellStatus::Used);
+        memory[addr].status = CellStatus::Used;
     }
 
-    fn visualize_allocation(memory: &mut [MemoryCell], addr: usize, size: usize) {
-        for c in memory.iter_mut().skip(addr).take(size) {
-            *c = MemoryCell::new(CellStatus::Allocated);
+    fn visualize_allocation(
+        memory: &mut [MemoryCell],
+        addr: usize,
+        header_words: usize,
+        size: usize,
+        pinned: bool,
+    ) {
+        let status = if pinned {
+            CellStatus::Pinned
+        } else {
+            CellStatus::Allocated
+        };
+        for c in memory.iter_mut().skip(addr).take(header_words) {
+            c.status = CellStatus::Header;
+        }
+        for c in memory.iter_mut().skip(addr + header_words).take(size) {
+            c.status = status;
         }
     }
 
     pub fn make_info_block(&self) -> InfoBlockData {
+        let heap = &self.vm.heap;
+        let large_object_space = heap.large_objects.as_ref().map(|los| LargeObjectSpaceInfo {
+            start: los.start,
+            page_size: los.page_size,
+            pages: los.pages.len(),
+            used_pages: los.used_pages(),
+            objects: heap.objects.range(los.start..).count(),
+        });
+        let large_objects = large_object_space.as_ref().map_or(0, |los| los.objects);
+        let size_classes = heap.size_classes.as_ref().map(|classes| {
+            let los_start = large_object_space
+                .as_ref()
+                .map_or(usize::MAX, |los| los.start);
+            classes
+                .classes
+                .iter()
+                .enumerate()
+                .map(|(i, class)| SizeClassInfo {
+                    size: class.size,
+                    free_cells: class.free.len(),
+                    used_cells: heap
+                        .objects
+                        .range(..los_start)
+                        .filter(|(_, obj)| classes.class_of(obj.size()) == Some(i))
+                        .count(),
+                })
+                .collect()
+        });
+        let buddy = heap.buddy.as_ref().map(|buddy| {
+            let allocated_blocks: Vec<BuddyBlockInfo> = buddy
+                .allocated
+                .iter()
+                .map(|(start, size)| BuddyBlockInfo {
+                    start: *start,
+                    size: *size,
+                    wasted: size - heap.objects.get(start).map_or(0, |obj| obj.size()),
+                })
+                .collect();
+            BuddyInfo {
+                free_blocks: buddy.free_blocks(),
+                wasted: allocated_blocks.iter().map(|block| block.wasted).sum(),
+                allocated_blocks,
+            }
+        });
         InfoBlockData {
             gc_type: self.rts_cfg.gc_ty.clone(),
             alignment: self.rts_cfg.alignment,
-            heap_size: self.rts_cfg.heap_size,
-            allocd_objects: self.vm.heap.objects.len(),
-            free_memory: self.vm.heap.calc_free_memory(),
+            heap_size: heap.memory.len(),
+            alloc_policy: self.rts_cfg.alloc_policy,
+            allocd_objects: heap.objects.len() - large_objects,
+            free_memory: heap.calc_free_memory(),
+            header_words: heap.header_overhead(),
+            large_object_space,
+            card_table: heap.card_table.clone(),
+            size_classes,
+            tlabs: heap.tlabs.clone(),
+            buddy,
         }
     }
 }
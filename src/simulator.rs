@@ -1,11 +1,13 @@
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 
 use rand::{distributions::WeightedIndex, prelude::Distribution, seq::SliceRandom, Rng};
 
 use crate::{
+    allocator::Allocator,
     error::VMError,
-    gc::init_collector,
-    object::{Field, ObjAddr, Object},
+    gc::{init_collector, GCType},
+    heap::Heap,
+    object::{Address, Field, ObjAddr, Object},
     program::{Instruction, Program},
     rts_cfg::ProgramRuntimeConfig,
     vm::VirtualMachine,
@@ -22,6 +24,10 @@ pub struct ProgGenConfig {
     pub prob_gc: f32,
     pub prob_write_scalar: f32,
     pub prob_write_pointer: f32,
+    /// Chance that a pointer write deliberately closes a reference cycle.
+    /// Only used for reference counting, where cycles are what the
+    /// cycle collector is there to demonstrate.
+    pub prob_write_cycle: f32,
 }
 
 impl Default for ProgGenConfig {
@@ -34,6 +40,7 @@ impl Default for ProgGenConfig {
             prob_gc: 0.05,
             prob_write_scalar: 0.5,
             prob_write_pointer: 0.5,
+            prob_write_cycle: 0.3,
         }
     }
 }
@@ -50,7 +57,15 @@ impl Simulator {
             vm: VirtualMachine::new(
                 rts_cfg.alignment,
                 rts_cfg.heap_size,
-                init_collector(&rts_cfg.gc_ty),
+                init_collector(&rts_cfg),
+                Allocator::new(
+                    rts_cfg.alloc_policy,
+                    rts_cfg.tlab_size,
+                    rts_cfg.object_header_words(),
+                ),
+                rts_cfg.gc_on_alloc_failure,
+                rts_cfg.heap_sizing,
+                rts_cfg.memory_model,
             ),
             rts_cfg,
             prog_gen_cfg: ProgGenConfig::default(),
@@ -86,15 +101,16 @@ impl Simulator {
         let object = Object::random();
         // TODO: hook up is_root generation
         let is_root = true;
-        match self
-            .vm
-            .allocator
-            .allocate(&mut self.vm.heap, object.clone(), is_root)
-        {
-            Ok(_) => Instruction::Allocate {
-                object,
-                is_root: true,
-            },
+        let instr = Instruction::Allocate {
+            object,
+            is_root,
+            finalizable: false,
+            thread: 0,
+        };
+        // Allocate the way the program will, so that the simulated heap
+        // goes through the same collector events and heap resizing
+        match self.vm.tick(&instr) {
+            Ok(_) => instr,
             Err(_) => panic!("gen_allocate"),
         }
     }
@@ -118,7 +134,7 @@ impl Simulator {
             } else {
                 let field_offset = valid_indexes.choose(&mut rng).unwrap();
                 Instruction::Read {
-                    addr: obj_addr + field_offset,
+                    addr: object.field_addr(obj_addr, *field_offset),
                 }
             }
         } else {
@@ -142,7 +158,7 @@ impl Simulator {
         {
             let object = &self.vm.heap.objects[&obj_addr];
             let field_offset = rng.gen_range(0..object.fields.len());
-            let address = obj_addr + field_offset;
+            let mut address = object.field_addr(obj_addr, field_offset);
 
             let p_scalar = self.prog_gen_cfg.prob_write_scalar;
             let p_pointer = self.prog_gen_cfg.prob_write_pointer;
@@ -153,21 +169,41 @@ impl Simulator {
                 rng.gen_range(0..9)
             } else {
                 // Write a pointer to another object with probability `prob_write_pointer`
-                let ref_chain = self.reference_chain(obj_addr);
-                let possible_addresses: Vec<ObjAddr> = self
-                    .vm
-                    .heap
-                    .objects
-                    .keys()
-                    .cloned()
-                    .filter(|a| {
-                        !ref_chain.contains(a)
-                            && self.vm.heap.objects[a]
-                                .fields
-                                .iter()
-                                .any(|field| matches!(field, Field::Scalar { value: _ }))
-                    })
-                    .collect();
+                let closes_cycle = matches!(self.rts_cfg.gc_ty, GCType::ReferenceCounting)
+                    && rng.gen_bool(self.prog_gen_cfg.prob_write_cycle as f64);
+                let possible_addresses: Vec<ObjAddr> = if closes_cycle {
+                    // Only a pointer field closes the cycle
+                    let object = &self.vm.heap.objects[&obj_addr];
+                    let ref_fields: Vec<usize> = object
+                        .fields
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, field)| matches!(field, Field::Ref { .. }))
+                        .map(|(idx, _)| idx)
+                        .collect();
+                    match ref_fields.choose(&mut rng) {
+                        Some(idx) => {
+                            address = object.field_addr(obj_addr, *idx);
+                            cycle_closing_targets(&self.vm.heap, obj_addr)
+                        }
+                        None => vec![],
+                    }
+                } else {
+                    let ref_chain = self.reference_chain(obj_addr);
+                    self.vm
+                        .heap
+                        .objects
+                        .keys()
+                        .cloned()
+                        .filter(|a| {
+                            !ref_chain.contains(a)
+                                && self.vm.heap.objects[a]
+                                    .fields
+                                    .iter()
+                                    .any(|field| matches!(field, Field::Scalar { value: _ }))
+                        })
+                        .collect()
+                };
 
                 if let Some(new_obj_addr) = possible_addresses.choose(&mut rng).cloned() {
                     new_obj_addr
@@ -230,3 +266,34 @@ impl Simulator {
         chain
     }
 }
+
+/// Objects from which `addr` is reachable through pointer fields, other
+/// than `addr` itself, so that pointing `addr` at any of them closes a
+/// cycle through at least two objects.
+pub fn cycle_closing_targets(heap: &Heap, addr: ObjAddr) -> Vec<ObjAddr> {
+    let mut referrers: HashMap<ObjAddr, Vec<ObjAddr>> = HashMap::new();
+    for (from, obj) in &heap.objects {
+        for field in &obj.fields {
+            if let Field::Ref {
+                addr: Address::Ptr(ptr),
+            } = field
+            {
+                if let Ok(to) = heap.lookup_object_addr(*ptr) {
+                    referrers.entry(to).or_default().push(*from);
+                }
+            }
+        }
+    }
+
+    // Follow the pointers backwards from `addr`
+    let mut reaching = BTreeSet::new();
+    let mut worklist = vec![addr];
+    while let Some(to) = worklist.pop() {
+        for from in referrers.get(&to).into_iter().flatten() {
+            if *from != addr && reaching.insert(*from) {
+                worklist.push(*from);
+            }
+        }
+    }
+    reaching.into_iter().collect()
+}
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    gc::{stats::TlabStats, GCEvent},
+    heap::Heap,
+    object::ObjAddr,
+};
+
+/// Thread-local allocation buffer: a chunk carved out of the free list that
+/// a single simulated thread bumps through, `[start, top)` being taken.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tlab {
+    pub start: ObjAddr,
+    pub top: ObjAddr,
+    pub end: ObjAddr,
+}
+
+/// Buffers of the simulated threads along with the cost of retiring them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tlabs {
+    /// Words carved out of the free list for each buffer.
+    pub size: usize,
+    /// Buffer of each thread that has one.
+    pub buffers: BTreeMap<usize, Tlab>,
+    /// Buffers retired since the last collection.
+    pub retired: usize,
+    /// Words those buffers left unused, including alignment padding.
+    pub waste: usize,
+    /// Those unused words, kept off the free list until the next collection
+    /// hands them back.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fillers: Vec<(ObjAddr, usize)>,
+}
+
+impl Tlabs {
+    pub fn new(size: usize) -> Self {
+        Tlabs {
+            size: size.max(1),
+            buffers: BTreeMap::new(),
+            retired: 0,
+            waste: 0,
+            fillers: vec![],
+        }
+    }
+}
+
+/// Bumps the buffer of `thread` past an object of `size` words, if it fits.
+/// Padding skipped for alignment is left behind as filler.
+pub fn bump(heap: &mut Heap, thread: usize, size: usize) -> Option<ObjAddr> {
+    let tlab = heap.tlabs.as_ref()?.buffers.get(&thread)?;
+    let top = tlab.top;
+    let start = heap.aligned_position(top);
+    if start + size > tlab.end {
+        return None;
+    }
+    if start > top {
+        let tlabs = heap.tlabs.as_mut()?;
+        tlabs.fillers.push((top, start - top));
+        tlabs.waste += start - top;
+    }
+    heap.tlabs.as_mut()?.buffers.get_mut(&thread)?.top = start + size;
+    Some(start)
+}
+
+/// Hands `[start, end)` to `thread` as its new buffer.
+pub fn install(
+    heap: &mut Heap,
+    thread: usize,
+    start: ObjAddr,
+    end: ObjAddr,
+    eventlog: &mut Vec<GCEvent>,
+) {
+    if let Some(tlabs) = heap.tlabs.as_mut() {
+        tlabs.buffers.insert(
+            thread,
+            Tlab {
+                start,
+                top: start,
+                end,
+            },
+        );
+        eventlog.push(GCEvent::NewTlab { thread, start, end });
+    }
+}
+
+/// Retires the buffer of `thread`. Its unused tail is left behind as filler
+/// and counted as waste: nobody can use it before the next collection.
+pub fn retire(heap: &mut Heap, thread: usize, eventlog: &mut Vec<GCEvent>) {
+    let Some(tlab) = heap
+        .tlabs
+        .as_mut()
+        .and_then(|tlabs| tlabs.buffers.remove(&thread))
+    else {
+        return;
+    };
+    let waste = tlab.end - tlab.top;
+    let tlabs = heap.tlabs.as_mut().unwrap();
+    if waste > 0 {
+        tlabs.fillers.push((tlab.top, waste));
+    }
+    tlabs.retired += 1;
+    tlabs.waste += waste;
+    eventlog.push(GCEvent::RetireTlab {
+        thread,
+        start: tlab.start,
+        end: tlab.end,
+        waste,
+    });
+}
+
+/// Retires every buffer and hands the fillers back to the free list, which
+/// a collection does before it looks at the free list or moves objects.
+pub fn retire_all(heap: &mut Heap) -> Vec<GCEvent> {
+    let mut eventlog = vec![];
+    let threads: Vec<usize> = heap
+        .tlabs
+        .as_ref()
+        .map_or(vec![], |tlabs| tlabs.buffers.keys().cloned().collect());
+    if !threads.is_empty() {
+        eventlog.push(GCEvent::GCPhase {
+            msg: "retire TLABs".to_string(),
+        });
+    }
+    for thread in threads {
+        retire(heap, thread, &mut eventlog);
+    }
+    let fillers = heap
+        .tlabs
+        .as_mut()
+        .map_or(vec![], |tlabs| std::mem::take(&mut tlabs.fillers));
+    for (addr, size) in fillers {
+        heap.free_list.insert(addr, size);
+    }
+    eventlog
+}
+
+/// Reports the buffers retired since the previous collection and starts
+/// counting anew.
+pub fn take_stats(heap: &mut Heap) -> Option<TlabStats> {
+    let tlabs = heap.tlabs.as_mut()?;
+    let stats = TlabStats {
+        retired: tlabs.retired,
+        waste: tlabs.waste,
+    };
+    tlabs.retired = 0;
+    tlabs.waste = 0;
+    Some(stats)
+}
@@ -1,10 +1,18 @@
-use crate::gc::GarbageCollector;
-use crate::{allocator::Allocator, heap::Heap, mutator::Mutator};
+use crate::gc::{finalization::run_finalizers, stats::GCStats, GCEvent, GarbageCollector};
+use crate::object::Finalization;
+use crate::{
+    allocator::Allocator,
+    heap::Heap,
+    heap_sizing::{self, HeapSizing},
+    mutator::Mutator,
+    tlab,
+    word::MemoryModel,
+};
 use crate::{
     error::VMError,
     program::{
         InstrResult,
-        Instruction::{self, Allocate, Read, Write, GC},
+        Instruction::{self, Allocate, Pin, Read, RunFinalizers, Unpin, Write, GC},
     },
 };
 
@@ -13,48 +21,182 @@ pub struct VirtualMachine {
     pub mutator: Mutator,
     pub collector: Box<dyn GarbageCollector>,
     pub heap: Heap,
+    /// Whether an allocation that does not fit triggers a collection and
+    /// is retried, instead of failing right away.
+    pub gc_on_alloc_failure: bool,
+    pub heap_sizing: HeapSizing,
+    /// Collections in a row that left the heap mostly free.
+    low_occupancy: usize,
 }
 
 impl VirtualMachine {
-    pub fn new(alignment: usize, heap_size: usize, gc: Box<dyn GarbageCollector>) -> Self {
+    pub fn new(
+        alignment: usize,
+        heap_size: usize,
+        gc: Box<dyn GarbageCollector>,
+        allocator: Allocator,
+        gc_on_alloc_failure: bool,
+        heap_sizing: HeapSizing,
+        memory_model: MemoryModel,
+    ) -> Self {
+        let mut collector = gc;
+        let mut heap = Heap::new(heap_size, alignment);
+        if memory_model == MemoryModel::Words {
+            heap.use_words();
+        }
+        collector.init_heap(&mut heap);
         VirtualMachine {
-            allocator: Allocator::new(),
+            allocator,
             mutator: Mutator::new(),
-            collector: gc,
-            heap: Heap::new(heap_size, alignment),
+            collector,
+            heap,
+            gc_on_alloc_failure,
+            heap_sizing,
+            low_occupancy: 0,
         }
     }
 
     pub fn tick(&mut self, instr: &Instruction) -> Result<InstrResult, VMError> {
+        let mut result = self.exec(instr)?;
+        if !matches!(instr, GC) {
+            let gc_eventlog = self.collector.step(&mut self.heap);
+            result.gc_eventlog_mut().extend(gc_eventlog);
+        }
+        Ok(result)
+    }
+
+    fn exec(&mut self, instr: &Instruction) -> Result<InstrResult, VMError> {
         match instr {
-            Allocate { object, is_root } => self
-                .allocator
-                .allocate(&mut self.heap, object.clone(), *is_root)
-                .map(|addr| InstrResult::Allocate {
+            Allocate {
+                object,
+                is_root,
+                finalizable,
+                thread,
+            } => {
+                let mut gc_eventlog = vec![];
+                let mut new_object = object.clone();
+                if *finalizable {
+                    new_object.header.finalization = Finalization::Pending;
+                }
+                let allocate = |vm: &mut Self, gc_eventlog: &mut Vec<GCEvent>| {
+                    vm.allocator.allocate_on_thread(
+                        &mut vm.heap,
+                        new_object.clone(),
+                        *is_root,
+                        *thread,
+                        gc_eventlog,
+                    )
+                };
+                let mut gc_stats = None;
+                let mut result = allocate(self, &mut gc_eventlog);
+                if result.is_err() && self.gc_on_alloc_failure {
+                    gc_eventlog.push(GCEvent::AllocationFailure {
+                        size: object.size(),
+                    });
+                    let (stats, eventlog) = self.collect()?;
+                    gc_eventlog.extend(eventlog);
+                    gc_stats = Some(stats);
+                    result = allocate(self, &mut gc_eventlog);
+                }
+                // Grow the heap for as long as the object does not fit
+                let sizing = self.sizing();
+                while result.is_err() {
+                    match heap_sizing::grow(&mut self.heap, sizing, object.size()) {
+                        Some(event) => gc_eventlog.push(event),
+                        None => break,
+                    }
+                    result = allocate(self, &mut gc_eventlog);
+                }
+                // Only a retry after collecting that fails too means the heap is full
+                let addr = result.map_err(|e| match e {
+                    VMError::AllocationError if self.gc_on_alloc_failure => VMError::OutOfMemory,
+                    e => e,
+                })?;
+                Ok(InstrResult::Allocate {
                     object: object.clone(),
                     addr,
-                }),
+                    gc_eventlog,
+                    gc_stats,
+                })
+            }
             Read { addr } => self
                 .mutator
                 .read(&self.heap, *addr)
-                .map(|value| InstrResult::Read { addr: *addr, value }),
+                .map(|value| InstrResult::Read {
+                    addr: *addr,
+                    value,
+                    gc_eventlog: vec![],
+                }),
             Write { addr, value } => {
                 self.mutator
                     .write(&mut self.heap, *addr, *value)
-                    .map(|()| InstrResult::Write {
+                    .map(|gc_eventlog| InstrResult::Write {
                         addr: *addr,
                         value: *value,
+                        gc_eventlog,
                     })
             }
-            GC => {
-                let result = self.collector.collect(&mut self.heap);
-                self.heap.merge_free_ranges();
-                result.map(|(stats, gc_eventlog)| InstrResult::GC { stats, gc_eventlog })
+            GC => self.collect().map(|(stats, gc_eventlog)| InstrResult::GC {
+                stats,
+                gc_eventlog,
+                side_tables: self.collector.side_tables(),
+                regions: self.heap.regions.clone(),
+                mark_bitmap: self.heap.mark_bitmap.clone(),
+            }),
+            RunFinalizers => Ok(InstrResult::RunFinalizers {
+                gc_eventlog: run_finalizers(&mut self.heap),
+            }),
+            Pin { addr } | Unpin { addr } => {
+                let pinned = matches!(instr, Pin { .. });
+                self.mutator
+                    .set_pinned(&mut self.heap, *addr, pinned)
+                    .map(|addr| InstrResult::Pin {
+                        addr,
+                        pinned,
+                        gc_eventlog: vec![],
+                    })
             }
         }
     }
 
+    /// Runs the collector, be it for a `GC` instruction or a failed allocation.
+    fn collect(&mut self) -> Result<(GCStats, Vec<GCEvent>), VMError> {
+        // Buffers are handed back before the collector looks at the free list
+        let mut gc_eventlog = tlab::retire_all(&mut self.heap);
+        let result = self.collector.collect(&mut self.heap);
+        self.heap.merge_free_ranges();
+        // Collectors update pointers in the object table, store them back
+        self.heap.sync_words();
+        result.map(|(mut stats, eventlog)| {
+            gc_eventlog.extend(eventlog);
+            stats.tlab = tlab::take_stats(&mut self.heap);
+            stats.header_words = Some(self.heap.header_overhead()).filter(|words| *words > 0);
+            let sizing = self.sizing();
+            gc_eventlog.extend(heap_sizing::shrink(
+                &mut self.heap,
+                sizing,
+                &mut self.low_occupancy,
+            ));
+            (stats, gc_eventlog)
+        })
+    }
+
+    /// The sizing policy in effect, `Fixed` if the collector cannot deal
+    /// with a heap that changes size.
+    fn sizing(&self) -> HeapSizing {
+        if self.collector.resizable_heap() {
+            self.heap_sizing
+        } else {
+            HeapSizing::Fixed
+        }
+    }
+
     pub fn reset_heap(&mut self, size: usize) {
+        let word_memory = self.heap.word_memory;
         self.heap = Heap::new(size, self.heap.alignment);
+        if word_memory {
+            self.heap.use_words();
+        }
+        self.collector.init_heap(&mut self.heap);
     }
 }
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::VMError,
+    object::{Address, Field, ObjAddr, Object},
+};
+
+/// Low bits of a word that tell what it holds, the rest is its payload.
+const TAG_BITS: u32 = 3;
+const TAG_MASK: u64 = (1 << TAG_BITS) - 1;
+/// Largest scalar that fits in the payload of a word.
+pub const MAX_SCALAR: usize = (u64::MAX >> TAG_BITS) as usize;
+
+const TAG_FREE: u64 = 0;
+const TAG_SCALAR: u64 = 1;
+const TAG_REF: u64 = 2;
+const TAG_WEAK_REF: u64 = 3;
+const TAG_EPHEMERON_KEY: u64 = 4;
+const TAG_EPHEMERON_VALUE: u64 = 5;
+const TAG_HEADER: u64 = 6;
+const TAG_FORWARD: u64 = 7;
+
+/// How `Heap::memory` is modelled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemoryModel {
+    /// Cells only carry the status the grid draws them with.
+    #[default]
+    Status,
+    /// Cells also hold the tagged word stored in them: object headers,
+    /// scalars and pointers are encoded into the heap. Scalars have to fit
+    /// in the payload of a word.
+    Words,
+}
+
+/// Decoded contents of a heap word.
+///
+/// A word is `payload << 3 | tag`. Pointer payloads are the address plus
+/// one, so that zero is the null pointer. A header holds the size of its
+/// object and the mark bit, until a forwarding pointer is written over it.
+#[derive(Clone, Debug)]
+pub enum Word {
+    Free,
+    Header { size: usize, marked: bool },
+    Forward { to: ObjAddr },
+    Field(Field),
+}
+
+/// Encodes `word`. Scalars are expected to fit in the payload, see
+/// `check_field`.
+pub fn encode(word: &Word) -> u64 {
+    let pointer = |addr: &Address| match addr {
+        Address::Ptr(addr) => *addr as u64 + 1,
+        Address::Null => 0,
+    };
+    let (payload, tag) = match word {
+        Word::Free => (0, TAG_FREE),
+        Word::Header { size, marked } => ((*size as u64) << 1 | u64::from(*marked), TAG_HEADER),
+        Word::Forward { to } => (*to as u64, TAG_FORWARD),
+        Word::Field(Field::Scalar { value }) => {
+            debug_assert!(
+                *value <= MAX_SCALAR,
+                "scalar {value} does not fit in a word"
+            );
+            (*value as u64, TAG_SCALAR)
+        }
+        Word::Field(Field::Ref { addr }) => (pointer(addr), TAG_REF),
+        Word::Field(Field::WeakRef { addr }) => (pointer(addr), TAG_WEAK_REF),
+        Word::Field(Field::EphemeronKey { addr }) => (pointer(addr), TAG_EPHEMERON_KEY),
+        Word::Field(Field::EphemeronValue { addr }) => (pointer(addr), TAG_EPHEMERON_VALUE),
+    };
+    payload << TAG_BITS | tag
+}
+
+/// Rejects scalars that would lose their top bits to the tag.
+pub fn check_field(field: &Field) -> Result<(), VMError> {
+    match field {
+        Field::Scalar { value } if *value > MAX_SCALAR => Err(VMError::WordOverflow(format!(
+            "Scalar {value} does not fit in a word, the largest is {MAX_SCALAR}",
+        ))),
+        _ => Ok(()),
+    }
+}
+
+pub fn decode(raw: u64) -> Word {
+    let payload = raw >> TAG_BITS;
+    let addr = match payload {
+        0 => Address::Null,
+        ptr => Address::Ptr(ptr as usize - 1),
+    };
+    match raw & TAG_MASK {
+        TAG_SCALAR => Word::Field(Field::Scalar {
+            value: payload as usize,
+        }),
+        TAG_REF => Word::Field(Field::Ref { addr }),
+        TAG_WEAK_REF => Word::Field(Field::WeakRef { addr }),
+        TAG_EPHEMERON_KEY => Word::Field(Field::EphemeronKey { addr }),
+        TAG_EPHEMERON_VALUE => Word::Field(Field::EphemeronValue { addr }),
+        TAG_HEADER => Word::Header {
+            size: (payload >> 1) as usize,
+            marked: payload & 1 == 1,
+        },
+        TAG_FORWARD => Word::Forward {
+            to: payload as usize,
+        },
+        _ => Word::Free,
+    }
+}
+
+/// Words of `object`, its header first. The first header word holds the
+/// forwarding pointer instead while the object has one, any further
+/// header words hold a blank header.
+pub fn encode_object(object: &Object) -> Vec<u64> {
+    let header = match object.header.fwd_addr {
+        Some(to) => Word::Forward { to },
+        None => Word::Header {
+            size: object.size(),
+            marked: object.header.marked,
+        },
+    };
+    let mut words: Vec<u64> = (0..object.header.words)
+        .map(|i| if i == 0 { encode(&header) } else { TAG_HEADER })
+        .collect();
+    words.extend(
+        object
+            .fields
+            .iter()
+            .map(|field| encode(&Word::Field(field.clone()))),
+    );
+    words
+}
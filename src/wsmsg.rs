@@ -1,4 +1,11 @@
-use crate::{gc::GCType, heap::MemoryCell, log::Log, program::InstrResult};
+use crate::{
+    allocator::AllocPolicy,
+    gc::{card_table::CardTable, GCType},
+    heap::MemoryCell,
+    log::Log,
+    program::InstrResult,
+    tlab::Tlabs,
+};
 use serde::{
     ser::{SerializeStruct, Serializer},
     Deserialize, Serialize,
@@ -25,8 +32,8 @@ pub enum WSMessageResponse {
         memory: Vec<MemoryCell>,
         log_entry: Option<Log>,
         pause_on_return: Option<bool>,
-        instr_result: Option<InstrResult>,
-        info_block: InfoBlockData,
+        instr_result: Option<Box<InstrResult>>,
+        info_block: Box<InfoBlockData>,
     },
     Halt,
 }
@@ -76,8 +83,8 @@ impl WSMessageResponse {
             memory,
             log_entry,
             pause_on_return,
-            instr_result,
-            info_block,
+            instr_result: instr_result.map(Box::new),
+            info_block: Box::new(info_block),
         }
     }
 
@@ -91,6 +98,59 @@ pub struct InfoBlockData {
     pub gc_type: GCType,
     pub alignment: usize,
     pub heap_size: usize,
+    pub alloc_policy: AllocPolicy,
     pub allocd_objects: usize,
     pub free_memory: usize,
+    /// Heap words taken up by the headers of the allocated objects.
+    #[serde(default)]
+    pub header_words: usize,
+    /// Occupancy of the large object space, counted apart from the
+    /// objects and free memory above.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub large_object_space: Option<LargeObjectSpaceInfo>,
+    /// Dirty cards, if the collector keeps a card table.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_table: Option<CardTable>,
+    /// Occupancy of each size class, if allocation uses segregated fits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_classes: Option<Vec<SizeClassInfo>>,
+    /// Thread-local allocation buffers in use, if allocation goes through them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tlabs: Option<Tlabs>,
+    /// Buddy blocks, if allocation uses the buddy system.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buddy: Option<BuddyInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LargeObjectSpaceInfo {
+    pub start: usize,
+    pub page_size: usize,
+    pub pages: usize,
+    pub used_pages: usize,
+    pub objects: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuddyInfo {
+    /// Free blocks as `(start, size)`.
+    pub free_blocks: Vec<(usize, usize)>,
+    pub allocated_blocks: Vec<BuddyBlockInfo>,
+    /// Internal fragmentation: words of allocated blocks no object uses.
+    pub wasted: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuddyBlockInfo {
+    pub start: usize,
+    pub size: usize,
+    /// Words past the end of the object the block was rounded up to.
+    pub wasted: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SizeClassInfo {
+    pub size: usize,
+    pub free_cells: usize,
+    pub used_cells: usize,
 }
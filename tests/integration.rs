@@ -7,26 +7,69 @@ use serde_json::to_value;
 use gcviz::{
     error::VMError,
     file_utils::{load_heap_snapshot, load_program, save_heap_snapshot, CURRENT_DIR},
+    gc::{stats::GCStats, GCEvent},
+    object::{Address, Field},
+    program::InstrResult,
     session::Session,
+    simulator::cycle_closing_targets,
+    word::{self, Word},
 };
 
 fn init_test(test_name: &str) -> Session {
-    let (program, rts_cfg) = load_program(test_name);
+    let (program, rts_cfg) = load_program(test_name).unwrap();
     let mut session = Session::new(rts_cfg);
     session.program = program;
     session
 }
 
+/// Loads the program over a session started with the default configuration,
+/// the way the frontend loads one.
+fn load_test(test_name: &str) -> Session {
+    let (program, rts_cfg) = load_program(test_name).unwrap();
+    let mut session = Session::default();
+    session.load(program, rts_cfg);
+    session
+}
+
 fn run_test(test: &mut Session) -> Result<(), VMError> {
+    while test.program.get(test.instr_ptr).is_some() {
+        test.tick()?;
+    }
+    Ok(())
+}
+
+/// Runs the program and returns the stats of its last collection.
+fn last_gc_stats(test_name: &str) -> Option<GCStats> {
+    let mut test = init_test(test_name);
+    let mut stats = None;
     while test.program.get(test.instr_ptr).is_some() {
         match test.tick() {
-            Err(e) => {
-                return Err(e);
-            }
+            Ok(InstrResult::GC {
+                stats: gc_stats, ..
+            }) => stats = Some(gc_stats),
+            // Collections triggered by a failed allocation count as well
+            Ok(InstrResult::Allocate {
+                gc_stats: Some(gc_stats),
+                ..
+            }) => stats = Some(gc_stats),
             _ => {}
         }
     }
-    Ok(())
+    stats
+}
+
+/// Whether running the program reclaims an object whose finalizer ran.
+fn reclaims_finalized(test_name: &str) -> bool {
+    let mut test = init_test(test_name);
+    let mut reclaimed = false;
+    while test.program.get(test.instr_ptr).is_some() {
+        if let Ok(InstrResult::GC { gc_eventlog, .. }) = test.tick() {
+            reclaimed |= gc_eventlog
+                .iter()
+                .any(|event| matches!(event, GCEvent::ReclaimFinalized { .. }));
+        }
+    }
+    reclaimed
 }
 
 fn check_against_snapshot(test_app: &Session, test_name: &str) {
@@ -50,6 +93,14 @@ fn __test(test_name: &str) -> Result<(), VMError> {
     Ok(())
 }
 
+/// Like `__test`, for a program loaded over a running session.
+fn __test_loaded(test_name: &str) -> Result<(), VMError> {
+    let mut test_app = load_test(test_name);
+    run_test(&mut test_app)?;
+    check_against_snapshot(&test_app, test_name);
+    Ok(())
+}
+
 #[test]
 fn test_simple() {
     assert!(__test("simple").is_ok());
@@ -80,6 +131,11 @@ fn test_alloc_3() {
     assert!(__test("alloc_3").is_ok());
 }
 
+#[test]
+fn test_alloc_best_fit() {
+    assert!(__test("alloc_best_fit").is_ok());
+}
+
 #[test]
 fn test_mark_sweep_1() {
     assert!(__test("mark_sweep_1").is_ok());
@@ -119,3 +175,514 @@ fn mark_compact_lisp2_1() {
 fn mark_compact_lisp2_2() {
     assert!(__test("mark_compact_lisp2_2").is_ok());
 }
+
+#[test]
+fn mark_compact_lisp2_relocate_all() {
+    let mut test = init_test("mark_compact_lisp2_relocate_all");
+    assert!(run_test(&mut test).is_ok());
+    // Both live objects slide down, not just the first one moved
+    let addrs: Vec<usize> = test.vm.heap.objects.keys().cloned().collect();
+    assert_eq!(addrs, vec![0, 2]);
+}
+
+#[test]
+fn mark_compact_jonkers_1() {
+    assert!(__test("mark_compact_jonkers_1").is_ok());
+}
+
+#[test]
+fn mark_compact_jonkers_2() {
+    assert!(__test("mark_compact_jonkers_2").is_ok());
+}
+
+#[test]
+fn mark_compact_compressor_1() {
+    assert!(__test("mark_compact_compressor_1").is_ok());
+}
+
+#[test]
+fn mark_compact_compressor_2() {
+    assert!(__test("mark_compact_compressor_2").is_ok());
+}
+
+#[test]
+fn test_copying_1() {
+    assert!(__test("copying_1").is_ok());
+}
+
+#[test]
+fn test_copying_2() {
+    assert!(__test("copying_2").is_ok());
+}
+
+#[test]
+fn test_copying_uneven_heap() {
+    assert!(load_program("copying_uneven_heap").is_err());
+}
+
+#[test]
+fn test_copying_to_space_overflow() {
+    let (program, mut rts_cfg) = load_program("copying_to_space_overflow").unwrap();
+    // Skips validation: to-space starts at 10, so evacuation pads the
+    // first survivor and the last one no longer fits
+    rts_cfg.heap_size = 20;
+    let mut test = Session::new(rts_cfg);
+    test.program = program;
+    assert_eq!(run_test(&mut test), Err(VMError::OutOfMemory));
+}
+
+#[test]
+fn test_generational_1() {
+    assert!(__test("generational_1").is_ok());
+}
+
+#[test]
+fn test_generational_2() {
+    assert!(__test("generational_2").is_ok());
+}
+
+#[test]
+fn test_generational_promote_coalesced() {
+    assert!(__test("generational_promote_coalesced").is_ok());
+}
+
+#[test]
+fn test_reference_counting_alloc_events() {
+    let mut test = init_test("reference_counting_alloc_events");
+    test.tick().unwrap();
+    // The pointer B is allocated with is counted as part of the allocation
+    let result = test.tick().unwrap();
+    assert!(matches!(
+        result,
+        InstrResult::Allocate { ref gc_eventlog, .. }
+            if matches!(gc_eventlog[..], [GCEvent::IncRef { addr: 0, count: 2 }])
+    ));
+}
+
+#[test]
+fn test_reference_counting_1() {
+    assert!(__test("reference_counting_1").is_ok());
+}
+
+#[test]
+fn test_reference_counting_2() {
+    assert!(__test("reference_counting_2").is_ok());
+}
+
+#[test]
+fn test_incremental_mark_sweep_dijkstra() {
+    assert!(__test("incremental_mark_sweep_dijkstra").is_ok());
+}
+
+#[test]
+fn test_incremental_mark_sweep_yuasa() {
+    assert!(__test("incremental_mark_sweep_yuasa").is_ok());
+}
+
+#[test]
+fn test_incremental_mark_sweep_no_barrier() {
+    // C is swept while black A still points to it
+    assert!(matches!(
+        __test("incremental_mark_sweep_no_barrier"),
+        Err(VMError::SegmentationFault)
+    ));
+}
+
+#[test]
+fn test_cycle_closing_targets() {
+    let mut test = init_test("cycle_closing_targets");
+    run_test(&mut test).unwrap();
+    let heap = &mut test.vm.heap;
+    // C is reached from A and B, D reaches nothing
+    assert_eq!(cycle_closing_targets(heap, 2), vec![0, 1]);
+    assert!(cycle_closing_targets(heap, 0).is_empty());
+    assert!(cycle_closing_targets(heap, 3).is_empty());
+
+    // Pointing C at A, as the generator would, closes A -> B -> C -> A
+    test.vm.mutator.write(heap, 2, 0).unwrap();
+    let mut cycle = vec![2];
+    while let Some(Field::Ref {
+        addr: Address::Ptr(next),
+    }) = heap.objects[cycle.last().unwrap()].fields.first()
+    {
+        if cycle.contains(next) {
+            break;
+        }
+        cycle.push(*next);
+    }
+    assert_eq!(cycle, vec![2, 0, 1]);
+    assert!(matches!(
+        heap.objects[&1].fields[0],
+        Field::Ref {
+            addr: Address::Ptr(2)
+        }
+    ));
+}
+
+#[test]
+fn test_incremental_unsupported_config() {
+    assert!(load_program("incremental_zero_budget").is_err());
+    assert!(load_program("incremental_mark_compact").is_err());
+    assert!(load_program("incremental_gc_workers").is_err());
+}
+
+#[test]
+fn test_compressor_unsupported_config() {
+    assert!(load_program("compressor_gc_workers").is_err());
+    assert!(load_program("compressor_mark_bitmap").is_err());
+}
+
+#[test]
+fn test_immix_1() {
+    assert!(__test("immix_1").is_ok());
+}
+
+#[test]
+fn test_immix_overflow() {
+    assert!(__test("immix_overflow").is_ok());
+}
+
+#[test]
+fn test_immix_2() {
+    assert!(__test("immix_2").is_ok());
+}
+
+#[test]
+fn test_treadmill_1() {
+    assert!(__test("treadmill_1").is_ok());
+}
+
+#[test]
+fn test_treadmill_2() {
+    assert!(__test("treadmill_2").is_ok());
+}
+
+#[test]
+fn test_treadmill_cell_size() {
+    assert!(__test("treadmill_cell_size").is_ok());
+}
+
+#[test]
+fn test_weak_ref_mark_sweep() {
+    assert!(__test("weak_ref_mark_sweep").is_ok());
+}
+
+#[test]
+fn test_weak_ref_reference_counting() {
+    assert!(__test("weak_ref_reference_counting").is_ok());
+}
+
+#[test]
+fn test_weak_ref_treadmill() {
+    assert!(__test("weak_ref_treadmill").is_ok());
+}
+
+#[test]
+fn test_weak_ref_mark_compact_lisp2() {
+    assert!(__test("weak_ref_mark_compact_lisp2").is_ok());
+}
+
+#[test]
+fn test_weak_ref_mark_compact_jonkers() {
+    assert!(__test("weak_ref_mark_compact_jonkers").is_ok());
+}
+
+#[test]
+fn test_weak_ref_copying() {
+    assert!(__test("weak_ref_copying").is_ok());
+}
+
+#[test]
+fn test_weak_ref_generational() {
+    assert!(__test("weak_ref_generational").is_ok());
+}
+
+#[test]
+fn test_weak_ref_immix() {
+    assert!(__test("weak_ref_immix").is_ok());
+}
+
+#[test]
+fn test_finalization_mark_sweep() {
+    assert!(__test("finalization_mark_sweep").is_ok());
+}
+
+#[test]
+fn test_finalization_mark_compact() {
+    assert!(__test("finalization_mark_compact").is_ok());
+}
+
+#[test]
+fn test_finalization_mark_compact_two_finger() {
+    assert!(__test("finalization_mark_compact_two_finger").is_ok());
+    assert!(reclaims_finalized("finalization_mark_compact_two_finger"));
+}
+
+#[test]
+fn test_finalization_mark_compact_lisp2() {
+    assert!(__test("finalization_mark_compact_lisp2").is_ok());
+    assert!(reclaims_finalized("finalization_mark_compact_lisp2"));
+}
+
+#[test]
+fn test_ephemeron_mark_sweep() {
+    assert!(__test("ephemeron_mark_sweep").is_ok());
+}
+
+#[test]
+fn test_ephemeron_copying() {
+    assert!(__test("ephemeron_copying").is_ok());
+}
+
+#[test]
+fn test_ephemeron_mark_compact_compressor() {
+    assert!(__test("ephemeron_mark_compact_compressor").is_ok());
+}
+
+#[test]
+fn test_ephemeron_mark_compact_jonkers() {
+    assert!(__test("ephemeron_mark_compact_jonkers").is_ok());
+}
+
+#[test]
+fn test_pinning_lisp2() {
+    assert!(__test("pinning_lisp2").is_ok());
+}
+
+#[test]
+fn test_pinning_two_fingers() {
+    assert!(__test("pinning_two_fingers").is_ok());
+}
+
+#[test]
+fn test_pinning_jonkers() {
+    assert!(__test("pinning_jonkers").is_ok());
+}
+
+#[test]
+fn test_pinning_compressor() {
+    assert!(__test("pinning_compressor").is_ok());
+}
+
+#[test]
+fn test_pinning_immix() {
+    assert!(__test("pinning_immix").is_ok());
+}
+
+#[test]
+fn test_large_objects_mark_sweep() {
+    assert!(__test("large_objects_mark_sweep").is_ok());
+}
+
+#[test]
+fn test_large_objects_mark_compact_lisp2() {
+    assert!(__test("large_objects_mark_compact_lisp2").is_ok());
+}
+
+#[test]
+fn test_large_objects_mark_compact_compressor() {
+    assert!(__test("large_objects_mark_compact_compressor").is_ok());
+}
+
+#[test]
+fn test_parallel_mark_wide() {
+    assert!(__test("parallel_mark_wide").is_ok());
+    let mark = last_gc_stats("parallel_mark_wide")
+        .and_then(|stats| stats.parallel_mark)
+        .unwrap();
+    // Stealing spreads the fan-out over every worker
+    assert_eq!(mark.rounds, 4);
+    assert!(mark.workers.iter().all(|worker| worker.marked > 0));
+    assert_eq!(
+        mark.workers
+            .iter()
+            .map(|worker| worker.steals)
+            .sum::<usize>(),
+        3
+    );
+}
+
+#[test]
+fn test_parallel_mark_deep() {
+    assert!(__test("parallel_mark_deep").is_ok());
+    let mark = last_gc_stats("parallel_mark_deep")
+        .and_then(|stats| stats.parallel_mark)
+        .unwrap();
+    // A list leaves nothing to steal, a single worker marks it all
+    assert_eq!(mark.rounds, 6);
+    assert_eq!(mark.workers[0].marked, 6);
+    assert!(mark.workers[1..].iter().all(|worker| worker.marked == 0));
+}
+
+#[test]
+fn test_mark_bitmap_mark_sweep() {
+    assert!(__test("mark_bitmap_mark_sweep").is_ok());
+}
+
+#[test]
+fn test_mark_bitmap_mark_compact_lisp2() {
+    assert!(__test("mark_bitmap_mark_compact_lisp2").is_ok());
+}
+
+#[test]
+fn test_card_table_generational() {
+    assert!(__test("card_table_generational").is_ok());
+    let cards = last_gc_stats("card_table_generational")
+        .and_then(|stats| stats.card_table)
+        .unwrap();
+    // Both pointer stores dirty a card, only the old one is scanned
+    assert_eq!(cards.dirtied, 2);
+    assert_eq!(cards.scanned, 1);
+}
+
+#[test]
+fn test_segregated_fits_mark_sweep() {
+    assert!(__test("segregated_fits_mark_sweep").is_ok());
+}
+
+#[test]
+fn test_segregated_fits_tlab() {
+    assert!(__test("segregated_fits_tlab").is_ok());
+}
+
+#[test]
+fn test_tlab_mark_compact_jonkers() {
+    assert!(__test("tlab_mark_compact_jonkers").is_ok());
+    let tlab = last_gc_stats("tlab_mark_compact_jonkers")
+        .and_then(|stats| stats.tlab)
+        .unwrap();
+    assert_eq!(tlab.retired, 2);
+    assert_eq!(tlab.waste, 1);
+}
+
+#[test]
+fn test_buddy_mark_sweep() {
+    assert!(__test("buddy_mark_sweep").is_ok());
+}
+
+#[test]
+fn test_buddy_moving_collector() {
+    // Moved objects would leave their buddy blocks allocated behind them
+    assert!(load_program("buddy_mark_compact").is_err());
+    assert!(load_program("buddy_copying").is_err());
+}
+
+#[test]
+fn test_gc_on_alloc_failure() {
+    assert!(__test("gc_on_alloc_failure").is_ok());
+    // The program has no GC instruction, the last allocation triggers one
+    assert!(last_gc_stats("gc_on_alloc_failure").is_some());
+}
+
+#[test]
+fn test_gc_on_alloc_failure_oom() {
+    assert_eq!(__test("gc_on_alloc_failure_oom"), Err(VMError::OutOfMemory));
+}
+
+#[test]
+fn test_gc_on_alloc_failure_deferred_collection() {
+    assert!(load_program("gc_on_alloc_failure_incremental").is_err());
+    assert!(load_program("gc_on_alloc_failure_treadmill").is_err());
+}
+
+#[test]
+fn test_heap_grow_mark_sweep() {
+    assert!(__test("heap_grow_mark_sweep").is_ok());
+}
+
+#[test]
+fn test_heap_shrink_mark_sweep() {
+    assert!(__test("heap_shrink_mark_sweep").is_ok());
+}
+
+#[test]
+fn test_header_words_mark_compact_jonkers() {
+    assert!(__test("header_words_mark_compact_jonkers").is_ok());
+    let stats = last_gc_stats("header_words_mark_compact_jonkers").unwrap();
+    // A and B keep a header word each
+    assert_eq!(stats.header_words, Some(2));
+}
+
+#[test]
+fn test_words_copying() {
+    assert!(__test("words_copying").is_ok());
+}
+
+#[test]
+fn test_heap_elastic_copying() {
+    assert!(__test("heap_elastic_copying").is_ok());
+}
+
+#[test]
+fn test_words_scalar_overflow() {
+    assert!(matches!(
+        __test("words_scalar_overflow"),
+        Err(VMError::WordOverflow(_))
+    ));
+    assert!(matches!(
+        __test("words_scalar_overflow_alloc"),
+        Err(VMError::WordOverflow(_))
+    ));
+}
+
+#[test]
+fn test_words_copying_cells() {
+    let mut test = init_test("words_copying_cells");
+    // Allocate A and B, then point A at B behind the object table's back
+    test.tick().unwrap();
+    test.tick().unwrap();
+    let b = Field::new_ref(Address::Ptr(2));
+    test.vm.heap.memory[1].word = Some(word::encode(&Word::Field(b)));
+    run_test(&mut test).unwrap();
+    // Copying traced the cell, so B survives and A is updated to its copy
+    let heap = &test.vm.heap;
+    assert!(heap.objects.contains_key(&10));
+    assert!(matches!(
+        heap.objects[&8].fields[0],
+        Field::Ref {
+            addr: Address::Ptr(10)
+        }
+    ));
+    assert!(matches!(
+        heap.word_at(9),
+        Some(Word::Field(Field::Ref {
+            addr: Address::Ptr(10)
+        }))
+    ));
+}
+
+#[test]
+fn test_load_program_alloc_policy() {
+    assert!(__test_loaded("alloc_best_fit").is_ok());
+    assert!(__test_loaded("buddy_mark_sweep").is_ok());
+}
+
+#[test]
+fn test_load_program_tlab() {
+    assert!(__test_loaded("tlab_mark_compact_jonkers").is_ok());
+}
+
+#[test]
+fn test_load_program_gc_on_alloc_failure() {
+    assert!(__test_loaded("gc_on_alloc_failure").is_ok());
+}
+
+#[test]
+fn test_load_program_heap_sizing() {
+    assert!(__test_loaded("heap_grow_mark_sweep").is_ok());
+    assert!(__test_loaded("heap_shrink_mark_sweep").is_ok());
+}
+
+#[test]
+fn test_load_program_header_words() {
+    assert!(__test_loaded("header_words_mark_compact_jonkers").is_ok());
+}
+
+#[test]
+fn test_load_program_memory_model() {
+    assert!(__test_loaded("words_copying").is_ok());
+    assert!(matches!(
+        __test_loaded("words_scalar_overflow"),
+        Err(VMError::WordOverflow(_))
+    ));
+}